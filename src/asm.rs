@@ -0,0 +1,571 @@
+use bitvec::prelude::*;
+use core::fmt;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::io_registers::io_registers;
+use crate::parser::{
+    build_instruction, comment_start, parse_line, Context, OperandKind, ParseError, Statement,
+};
+use crate::preprocess::{preprocess, SourceLine};
+use crate::symbol::BitAddress;
+
+#[derive(Debug, Clone)]
+pub struct AsmError {
+    // the file the line came from, None for the top level source passed to
+    // assemble()
+    pub file: Option<PathBuf>,
+    // 1-based line number in that file
+    pub line: usize,
+    pub error: ParseError,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        if let Some(file) = &self.file {
+            write!(fmt, "{}: ", file.display())?;
+        }
+        write!(fmt, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone)]
+pub struct Assembly {
+    // address of the first word
+    pub origin: BitAddress,
+    // a trailing partial word from .field/.string is zero padded
+    pub words: Vec<u16>,
+    // how many of those bits were laid down, an odd .byte at the end makes
+    // for an odd number of bytes
+    pub bits: usize,
+    // label values are bit addresses
+    pub symbols: HashMap<String, i64>,
+}
+
+impl Assembly {
+    // little endian, the same byte order disassemble_stage1 reads
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self
+            .words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect();
+        bytes.truncate(self.bits.div_ceil(8));
+        bytes
+    }
+}
+
+// What a line puts in memory: (value, width in bits) pieces laid down LSB
+// first starting at the next multiple of `align` bits. Instructions are just
+// a run of 16 bit pieces.
+struct Emit {
+    align: u32,
+    fields: Vec<(u32, u32)>,
+}
+
+impl Emit {
+    fn bits(&self) -> u32 {
+        self.fields.iter().map(|(_, width)| width).sum()
+    }
+}
+
+struct Line {
+    source: SourceLine,
+    statement: Statement,
+    // set once the line has changed size between passes, from then on it
+    // always uses the long encoding so the layout can settle
+    long: bool,
+    size: Option<u32>,
+}
+
+impl Line {
+    fn error(&self, error: ParseError) -> AsmError {
+        self.source.error(error)
+    }
+
+    fn is_directive(&self) -> bool {
+        self.statement
+            .mnemonic
+            .as_ref()
+            .is_some_and(|mnemonic| mnemonic.starts_with('.'))
+    }
+
+    // .set/.equ give their label a value rather than an address
+    fn is_assignment(&self) -> bool {
+        self.statement.mnemonic.as_ref().is_some_and(|mnemonic| {
+            mnemonic.eq_ignore_ascii_case(".set") || mnemonic.eq_ignore_ascii_case(".equ")
+        })
+    }
+
+    // where the line will start without having to build it, so the labels
+    // in front of it are known to its own operands. None for .align, .set
+    // and .equ.
+    fn start(&self, base: u32, loc: u32) -> Option<u32> {
+        let align = match self
+            .statement
+            .mnemonic
+            .as_deref()?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            ".align" | ".set" | ".equ" => return None,
+            ".field" | ".string" | ".byte" | ".space" => 1,
+            _ => 16,
+        };
+        Some(align_up(base.wrapping_add(loc), align).wrapping_sub(base))
+    }
+}
+
+// How a name got its value. Labels and .equ are fixed once given, .set can
+// be given a new value any number of times, e.g. to count in a .loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Binding {
+    Label,
+    Set,
+    Equ,
+}
+
+impl Binding {
+    pub(crate) fn of(mnemonic: Option<&str>) -> Self {
+        match mnemonic {
+            Some(m) if m.eq_ignore_ascii_case(".set") => Binding::Set,
+            Some(m) if m.eq_ignore_ascii_case(".equ") => Binding::Equ,
+            _ => Binding::Label,
+        }
+    }
+
+    pub(crate) fn can_redefine(self, new: Binding) -> bool {
+        self == Binding::Set && new == Binding::Set
+    }
+}
+
+pub(crate) fn already_defined(name: &str, span: core::ops::Range<usize>) -> ParseError {
+    ParseError::new(format!("label '{}' is already defined", name), span)
+}
+
+// The symbols as of the line being assembled. Values come from what this
+// pass has defined so far, then from the previous pass for anything further
+// on. Sizes can only depend on the first kind.
+struct Scope {
+    values: HashMap<String, i64>,
+    known: HashMap<String, i64>,
+    bindings: HashMap<String, Binding>,
+}
+
+impl Scope {
+    fn new(previous: &HashMap<String, i64>) -> Self {
+        // the I/O registers can be used by name, unless the source has its
+        // own use for one
+        let mut values: HashMap<_, _> = io_registers()
+            .map(|(name, address)| (name.to_string(), address.0 as i64))
+            .collect();
+        values.extend(previous.iter().map(|(name, value)| (name.clone(), *value)));
+        Scope {
+            values,
+            known: HashMap::new(),
+            bindings: HashMap::new(),
+        }
+    }
+
+    fn define(&mut self, line: &Line, value: i64) -> Result<(), AsmError> {
+        let label = line.statement.label.as_ref().unwrap();
+        let binding = Binding::of(line.statement.mnemonic.as_deref());
+        if let Some(old) = self.bindings.insert(label.clone(), binding) {
+            if !old.can_redefine(binding) {
+                return Err(line.error(already_defined(label, line.statement.label_span.clone())));
+            }
+        }
+        self.values.insert(label.clone(), value);
+        self.known.insert(label.clone(), value);
+        Ok(())
+    }
+}
+
+fn align_up(bit: u32, align: u32) -> u32 {
+    bit.wrapping_add(align - 1) & !(align - 1)
+}
+
+fn directive(
+    statement: &Statement,
+    ctx: &Context,
+    known: &HashMap<String, i64>,
+) -> Result<Emit, ParseError> {
+    let mnemonic = statement
+        .mnemonic
+        .as_deref()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let ops = &statement.operands;
+    // anything that changes the size of the output can only use what's
+    // been defined above it, otherwise the layout might never settle
+    let sizing = Context {
+        symbols: known,
+        forward: false,
+        ..*ctx
+    };
+    // before the final pass only the sizes matter, so don't trip over values
+    // that are still pointing at stale or placeholder addresses
+    let value = |op, what, min, max| match ctx.ranged(op, what, min, max) {
+        Err(_) if ctx.forward => Ok(0),
+        result => result,
+    };
+    let bad_operands = || {
+        ParseError::new(
+            format!("invalid operands for {}", mnemonic),
+            statement.mnemonic_span.clone(),
+        )
+    };
+
+    let emit = match mnemonic.as_str() {
+        ".word" | ".long" | ".field" | ".string" | ".byte" if ops.is_empty() => {
+            return Err(bad_operands())
+        }
+        ".word" => Emit {
+            align: 16,
+            fields: ops
+                .iter()
+                .map(|op| {
+                    Ok((
+                        value(op, "word", i16::MIN as i64, u16::MAX as i64)? as u32,
+                        16,
+                    ))
+                })
+                .collect::<Result<_, ParseError>>()?,
+        },
+        ".long" => Emit {
+            align: 16,
+            fields: ops
+                .iter()
+                .map(|op| {
+                    Ok((
+                        value(op, "long", i32::MIN as i64, u32::MAX as i64)? as u32,
+                        32,
+                    ))
+                })
+                .collect::<Result<_, ParseError>>()?,
+        },
+        ".field" => {
+            if ops.len() > 2 {
+                return Err(bad_operands());
+            }
+            let width = match ops.get(1) {
+                Some(op) => sizing.ranged(op, "field width", 1, 32)? as u32,
+                None => 32,
+            };
+            // allow both the signed and unsigned range of the field
+            let min = -(1i64 << (width - 1));
+            let max = (1i64 << width) - 1;
+            let field = value(&ops[0], "field", min, max)? as u32;
+            Emit {
+                align: 1,
+                fields: vec![(field, width)],
+            }
+        }
+        // .byte is the same thing, TI accepts text in either
+        ".string" | ".byte" => {
+            let mut fields = vec![];
+            for op in ops {
+                match &op.kind {
+                    OperandKind::String(text) => {
+                        fields.extend(text.bytes().map(|byte| (byte as u32, 8)));
+                    }
+                    _ => {
+                        fields.push((value(op, "byte", i8::MIN as i64, u8::MAX as i64)? as u32, 8))
+                    }
+                }
+            }
+            Emit { align: 1, fields }
+        }
+        ".space" => match ops.as_slice() {
+            // the size is in bits, like everything else on this chip
+            [op] => {
+                let mut bits = sizing.ranged(op, "size", 0, u32::MAX as i64)? as u32;
+                let mut fields = vec![];
+                while bits > 0 {
+                    let width = bits.min(32);
+                    fields.push((0, width));
+                    bits -= width;
+                }
+                Emit { align: 1, fields }
+            }
+            _ => return Err(bad_operands()),
+        },
+        ".even" if ops.is_empty() => Emit {
+            align: 16,
+            fields: vec![],
+        },
+        ".align" => match ops.as_slice() {
+            [] => Emit {
+                align: 16,
+                fields: vec![],
+            },
+            [op] => {
+                let align = sizing.ranged(op, "alignment", 1, 1 << 31)? as u32;
+                if !align.is_power_of_two() {
+                    return Err(ParseError::new(
+                        "alignment must be a power of two",
+                        op.span.clone(),
+                    ));
+                }
+                Emit {
+                    align,
+                    fields: vec![],
+                }
+            }
+            _ => return Err(bad_operands()),
+        },
+        ".even" => return Err(bad_operands()),
+        ".set" | ".equ" => match ops.as_slice() {
+            [op] if statement.label.is_some() => {
+                value(op, "value", i64::MIN, i64::MAX)?;
+                Emit {
+                    align: 1,
+                    fields: vec![],
+                }
+            }
+            _ => return Err(bad_operands()),
+        },
+        _ => {
+            return Err(ParseError::new(
+                format!("unknown directive '{}'", mnemonic),
+                statement.mnemonic_span.clone(),
+            ))
+        }
+    };
+    Ok(emit)
+}
+
+// Builds whatever the line emits at bit offset `loc` from the origin,
+// returning the offset it actually starts at after alignment
+fn build_line(
+    line: &Line,
+    origin: BitAddress,
+    loc: u32,
+    scope: &Scope,
+    long: bool,
+    forward: bool,
+) -> Result<(u32, Emit), ParseError> {
+    let base = origin.0;
+    let symbols = &scope.values;
+    if line.is_directive() {
+        let ctx = Context {
+            pc: BitAddress(base.wrapping_add(loc)),
+            symbols,
+            long,
+            forward,
+        };
+        let emit = directive(&line.statement, &ctx, &scope.known)?;
+        // alignment is relative to the address space, not the origin
+        let start = align_up(base.wrapping_add(loc), emit.align).wrapping_sub(base);
+        Ok((start, emit))
+    } else {
+        let start = align_up(base.wrapping_add(loc), 16).wrapping_sub(base);
+        let ctx = Context {
+            pc: BitAddress(base.wrapping_add(start)),
+            symbols,
+            long,
+            forward,
+        };
+        let inst = build_instruction(&line.statement, &ctx)?;
+        let fields = inst
+            .encode()
+            .into_iter()
+            .map(|word| (word as u32, 16))
+            .collect();
+        Ok((start, Emit { align: 16, fields }))
+    }
+}
+
+// Lays out every line with the symbols from the previous run, returning the
+// new symbol table and whether any line had to switch to its long form. The
+// final run is given `out` and writes the bits there, reporting any errors.
+fn layout(
+    lines: &mut [Line],
+    origin: BitAddress,
+    symbols: &HashMap<String, i64>,
+    mut out: Option<&mut BitVec<u16, Lsb0>>,
+) -> Result<(HashMap<String, i64>, bool), AsmError> {
+    let base = origin.0;
+    let mut scope = Scope::new(symbols);
+    let forward = out.is_none();
+    let mut changed = false;
+    // bit offset from the origin
+    let mut loc = 0u32;
+    // labels on lines of their own belong to whatever comes next, after it
+    // has been aligned
+    let mut pending: Vec<usize> = vec![];
+    for i in 0..lines.len() {
+        if let Some(start) = lines[i].start(base, loc) {
+            for j in pending.drain(..) {
+                scope.define(&lines[j], base.wrapping_add(start) as i64)?;
+            }
+        }
+        let line = &mut lines[i];
+        let mut placed = None;
+        if let Some(bits) = out.as_deref_mut() {
+            if line.statement.mnemonic.is_some() {
+                let (start, emit) = build_line(line, origin, loc, &scope, line.long, false)
+                    .map_err(|error| line.error(error))?;
+                placed = Some((start, emit.bits()));
+                bits.resize(start as usize, false);
+                for (value, width) in emit.fields {
+                    bits.extend_from_bitslice(&value.view_bits::<Lsb0>()[..width as usize]);
+                }
+            }
+        } else if line.is_directive() {
+            let (start, emit) = build_line(line, origin, loc, &scope, false, true)
+                .map_err(|error| line.error(error))?;
+            placed = Some((start, emit.bits()));
+        } else if line.statement.mnemonic.is_some() {
+            for long in [line.long, true] {
+                // errors here may just be stale addresses, the final pass
+                // reports whatever is still wrong
+                if let Ok((start, emit)) = build_line(line, origin, loc, &scope, long, true) {
+                    let bits = emit.bits();
+                    if long || line.size.is_none_or(|prev| prev == bits) {
+                        placed = Some((start, bits));
+                        if long && !line.long {
+                            line.long = true;
+                            changed = true;
+                        }
+                        break;
+                    }
+                }
+            }
+            if placed.is_none() {
+                let start = align_up(base.wrapping_add(loc), 16).wrapping_sub(base);
+                placed = Some((start, line.size.unwrap_or(16)));
+            }
+        }
+        let (start, bits) = placed.unwrap_or((loc, 0));
+        line.size = Some(bits);
+        loc = start.wrapping_add(bits);
+
+        let line = &lines[i];
+        if line.statement.label.is_some() && line.statement.mnemonic.is_none() {
+            pending.push(i);
+            continue;
+        }
+        if !line.is_assignment() {
+            for j in pending.drain(..) {
+                scope.define(&lines[j], base.wrapping_add(start) as i64)?;
+            }
+        }
+        if line.statement.label.is_some() {
+            let address = if line.is_assignment() {
+                // the preprocessor already worked out anything that doesn't
+                // depend on an address, errors in the rest were reported by
+                // directive() on the final pass
+                let ctx = Context {
+                    pc: BitAddress(base.wrapping_add(start)),
+                    symbols: &scope.values,
+                    long: false,
+                    forward,
+                };
+                line.source.value.unwrap_or_else(|| {
+                    line.statement
+                        .operands
+                        .first()
+                        .and_then(|op| ctx.ranged(op, "value", i64::MIN, i64::MAX).ok())
+                        .unwrap_or(0)
+                })
+            } else {
+                base.wrapping_add(start) as i64
+            };
+            scope.define(line, address)?;
+        }
+    }
+    for j in pending {
+        scope.define(&lines[j], base.wrapping_add(loc) as i64)?;
+    }
+    Ok((scope.known, changed))
+}
+
+// .include/.copy paths are relative to the current directory
+pub fn assemble(source: &str, origin: BitAddress) -> Result<Assembly, Error> {
+    assemble_from(source, None, origin)
+}
+
+// Same as assemble() for source read from `path`, which errors then name and
+// .include/.copy paths are relative to
+pub fn assemble_from(
+    source: &str,
+    path: Option<&Path>,
+    origin: BitAddress,
+) -> Result<Assembly, Error> {
+    let mut lines = vec![];
+    for source in preprocess(source, path)? {
+        let statement = parse_line(&source.text).map_err(|error| source.error(error))?;
+        lines.push(Line {
+            source,
+            statement,
+            long: false,
+            size: None,
+        });
+    }
+
+    // pass 1, repeated until every label has stopped moving
+    let mut symbols = HashMap::new();
+    loop {
+        let (defined, changed) = layout(&mut lines, origin, &symbols, None)?;
+        let settled = !changed && defined == symbols;
+        symbols = defined;
+        if settled {
+            break;
+        }
+    }
+
+    // pass 2
+    let mut bits: BitVec<u16, Lsb0> = BitVec::new();
+    layout(&mut lines, origin, &symbols, Some(&mut bits))?;
+
+    Ok(Assembly {
+        origin,
+        bits: bits.len(),
+        words: bits.into_vec(),
+        symbols,
+    })
+}
+
+// Reassembles the text disassemble_stage2 produces. The address on the first
+// line sets the origin and the hexdump column is ignored, so the bytes only
+// come back the same if every instruction displays unambiguously.
+pub fn assemble_listing(listing: &str, path: Option<&Path>) -> Result<Assembly, Error> {
+    let mut origin = None;
+    let mut source = String::new();
+    for (i, line) in listing.lines().enumerate() {
+        if line.trim().is_empty() {
+            source.push('\n');
+            continue;
+        }
+        let error = |message: &str| AsmError {
+            file: path.map(Path::to_path_buf),
+            line: i + 1,
+            error: ParseError::new(message, 0..line.len()),
+        };
+        let (address, rest) = match line.split_once(":\t") {
+            Some(split) => split,
+            None => return Err(error("expected an address followed by a tab").into()),
+        };
+        let address = match u32::from_str_radix(address, 16) {
+            Ok(address) if address % 16 == 0 => address,
+            _ => return Err(error("invalid address").into()),
+        };
+        origin.get_or_insert(BitAddress(address));
+        // drop any comment and then the hexdump before it, no instruction
+        // ends in a bare 4 digit hex word
+        let mut text = rest[..comment_start(rest)].trim_end();
+        while let Some((head, word)) = text.rsplit_once(' ') {
+            if word.len() != 4 || !word.chars().all(|c| c.is_ascii_hexdigit()) {
+                break;
+            }
+            text = head.trim_end();
+        }
+        source.push_str(text);
+        source.push('\n');
+    }
+    assemble_from(&source, path, origin.unwrap_or_default())
+}
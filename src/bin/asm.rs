@@ -0,0 +1,124 @@
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use clap::{Arg, Command};
+use tms34010_asm::asm::{assemble_from, assemble_listing};
+use tms34010_asm::expr::parse_ranged;
+use tms34010_asm::symbol::BitAddress;
+
+fn main() {
+    let matches = Command::new("TMS34010 Assembler")
+        .author("Paul Sajna, hello@paulsajna.com")
+        .version("0.1.0")
+        .about("Assembler for Texas Instruments TMS34010 CPU")
+        .arg(
+            Arg::new("in_file")
+                .help("Source file to assemble")
+                .required(true),
+        )
+        .arg(
+            Arg::new("out_file")
+                .help("Binary file to write")
+                .required(true),
+        )
+        .arg(
+            Arg::new("start_pc")
+                .help("Bit address of the first assembled word, e.g. FFC00000h, same as the disassembler's --pc")
+                .takes_value(true)
+                .default_value("0")
+                .short('p')
+                .long("pc"),
+        )
+        .arg(
+            Arg::new("fill")
+                .help("Byte used to pad the output up to --size")
+                .takes_value(true)
+                .default_value("0xFF")
+                .short('f')
+                .long("fill"),
+        )
+        .arg(
+            Arg::new("size")
+                .help("Pad the output to N bytes, e.g. the size of the ROM being replaced")
+                .takes_value(true)
+                .short('s')
+                .long("size"),
+        )
+        .arg(
+            Arg::new("listing")
+                .help("The input is tms34010-disasm output, the addresses in it replace --pc")
+                .short('l')
+                .long("listing"),
+        )
+        .get_matches();
+
+    let in_file = Path::new(matches.value_of("in_file").unwrap());
+    let out_file = Path::new(matches.value_of("out_file").unwrap());
+
+    let start_pc = match matches.value_of("start_pc").unwrap().parse::<BitAddress>() {
+        Ok(address) if address.is_word_aligned() => address,
+        Ok(address) => {
+            eprintln!("start_pc {} is not on a word boundary", address);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("start_pc {}", e);
+            process::exit(1);
+        }
+    };
+
+    let fill: u8 = match parse_ranged(matches.value_of("fill").unwrap()) {
+        Ok(fill) => fill,
+        Err(e) => {
+            eprintln!("fill {}", e);
+            process::exit(1);
+        }
+    };
+    let size: usize = match matches.value_of("size").map(parse_ranged).transpose() {
+        Ok(size) => size.unwrap_or(0),
+        Err(e) => {
+            eprintln!("size {}", e);
+            process::exit(1);
+        }
+    };
+
+    let source = match fs::read_to_string(in_file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}: {}", in_file.display(), e);
+            process::exit(1);
+        }
+    };
+
+    let result = if matches.is_present("listing") {
+        assemble_listing(&source, Some(in_file))
+    } else {
+        assemble_from(&source, Some(in_file), start_pc)
+    };
+    let assembly = match result {
+        Ok(assembly) => assembly,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut bytes = assembly.to_bytes();
+    if size > 0 {
+        if bytes.len() > size {
+            eprintln!(
+                "assembled output is {} bytes, which doesn't fit in {} bytes",
+                bytes.len(),
+                size
+            );
+            process::exit(1);
+        }
+        bytes.resize(size, fill);
+    }
+
+    if let Err(e) = fs::write(out_file, &bytes) {
+        eprintln!("{}: {}", out_file.display(), e);
+        process::exit(1);
+    }
+}
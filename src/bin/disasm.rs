@@ -1,10 +1,22 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::SeekFrom;
+use std::io::{self, ErrorKind, SeekFrom};
 use std::path::Path;
+use std::process;
 
 use clap::{Arg, Command};
-use tms34010_asm::disasm::disassemble;
+use tms34010_asm::callgraph::{function_labels, CallGraph};
+use tms34010_asm::disasm::{
+    auto_labels, disassemble_stage1, to_words, try_disassemble_stage2_with, Listing,
+};
+use tms34010_asm::expr::parse_ranged;
+use tms34010_asm::fields::{field_entries, track_fields};
+use tms34010_asm::flow::{disassemble_flow, flow_entries};
+use tms34010_asm::symbol::BitAddress;
+use tms34010_asm::symbol_file::{load_symbols, symbol_kinds, symbol_labels, SymbolKind};
+use tms34010_asm::vectors::{read_vectors, vector_labels};
+use tms34010_asm::Error;
 
 fn main() {
     let matches = Command::new("TMS34010 Disassembler")
@@ -26,7 +38,7 @@ fn main() {
         )
         .arg(
             Arg::new("start_pc")
-                .help("Initial program counter at start of file or seek address")
+                .help("Bit address of the first byte read, as shown in the listing, e.g. FFC00000h")
                 .takes_value(true)
                 .default_value("0")
                 .short('p')
@@ -39,56 +51,203 @@ fn main() {
                 .short('s')
                 .long("size"),
         )
+        .arg(
+            Arg::new("flow")
+                .help("Only disassemble code reachable from the entry points and trap vectors, the rest is data")
+                .long("flow"),
+        )
+        .arg(
+            Arg::new("cfg")
+                .help("Print the control flow graph of what --flow would disassemble as Graphviz DOT instead of the listing")
+                .long("cfg"),
+        )
+        .arg(
+            Arg::new("call_graph")
+                .help("Print the functions found from the entry points, trap vectors, calls and MMTM SP prologues, with their extents, callers and callees, as text or Graphviz DOT instead of the listing (implies --flow)")
+                .takes_value(true)
+                .possible_values(["text", "dot"])
+                .long("call-graph"),
+        )
+        .arg(
+            Arg::new("no_labels")
+                .help("Don't generate L_/SUB_ labels or name trap handlers and I/O registers, only use --symbols")
+                .long("no-labels"),
+        )
+        .arg(
+            Arg::new("implicit")
+                .help("Comment PIXBLT, FILL, LINE and the other graphics instructions with the B registers they use implicitly")
+                .long("implicit"),
+        )
+        .arg(
+            Arg::new("fields")
+                .help("Follow SETF from the entry points and comment field moves, SEXT, ZEXT and EXGF with the field they use, e.g. 8-bit zext")
+                .long("fields"),
+        )
+        .arg(
+            Arg::new("symbols")
+                .help("File of \"address name [code|data|word|long]\" lines naming addresses in the listing, code ones are also --flow entry points")
+                .takes_value(true)
+                .long("symbols"),
+        )
+        .arg(
+            Arg::new("entry")
+                .help("Bit address to start following code from with --flow, can be repeated")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .short('e')
+                .long("entry"),
+        )
         .get_matches();
 
     let in_file = Path::new(matches.value_of("in_file").unwrap());
 
-    let offset: u64;
-    let str_offset = matches.value_of("offset").unwrap();
-    if str_offset.starts_with("0x") {
-        offset = u64::from_str_radix(str_offset.strip_prefix("0x").unwrap(), 16)
-            .expect("Offset is not a valid hexadecimal number");
-    } else {
-        offset = str_offset
-            .parse::<u64>()
-            .expect("Offset is not a valid number");
-    }
+    let offset: u64 = match parse_ranged(matches.value_of("offset").unwrap()) {
+        Ok(offset) => offset,
+        Err(e) => {
+            eprintln!("offset {}", e);
+            process::exit(1);
+        }
+    };
 
-    let start_pc: usize;
-    let str_start_pc = matches.value_of("start_pc").unwrap();
-    if str_start_pc.starts_with("0x") {
-        start_pc = usize::from_str_radix(str_start_pc.strip_prefix("0x").unwrap(), 16)
-            .expect("start_pc is not a valid hexadecimal number");
-    } else {
-        start_pc = str_start_pc
-            .parse::<usize>()
-            .expect("start_pc is not a valid number");
+    let start_pc = match matches.value_of("start_pc").unwrap().parse::<BitAddress>() {
+        Ok(address) if address.is_word_aligned() => address,
+        Ok(address) => {
+            eprintln!("start_pc {} is not on a word boundary", address);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("start_pc {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut entries = vec![];
+    for entry in matches.values_of("entry").into_iter().flatten() {
+        match entry.parse::<BitAddress>() {
+            Ok(address) => entries.push(address),
+            Err(e) => {
+                eprintln!("entry {}", e);
+                process::exit(1);
+            }
+        }
     }
 
-    let mut size: u64 = 0;
-    if let Some(str_size) = matches.value_of("size") {
-        if str_size.starts_with("0x") {
-            size = u64::from_str_radix(str_size.strip_prefix("0x").unwrap(), 16)
-                .expect("size is not a valid hexadecimal number");
-        } else {
-            size = str_size
-                .parse::<u64>()
-                .expect("size is not a valid number");
+    let symbols = match matches.value_of("symbols") {
+        Some(path) => match load_symbols(Path::new(path)) {
+            Ok(symbols) => symbols,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        None => vec![],
+    };
+    entries.extend(
+        symbols
+            .iter()
+            .filter(|symbol| symbol.kind == Some(SymbolKind::Code))
+            .map(|symbol| symbol.address),
+    );
+
+    let size: u64 = match matches.value_of("size").map(parse_ranged).transpose() {
+        Ok(size) => size.unwrap_or(0),
+        Err(e) => {
+            eprintln!("size {}", e);
+            process::exit(1);
         }
+    };
+
+    let mut buffer = vec![];
+    let result = File::open(in_file).and_then(|mut file| {
+        let buffer_size = if size > 0 {
+            size
+        } else {
+            file.metadata()?.len()
+        };
+        file.seek(SeekFrom::Start(offset))?;
+        file.take(buffer_size).read_to_end(&mut buffer)
+    });
+    if let Err(e) = result {
+        eprintln!("{}: {}", in_file.display(), e);
+        process::exit(1);
     }
 
-    let mut file = File::open(in_file).expect("Unable to open input file");
-    let buffer_size = if size > 0 {
-        size
+    // the graphs only make sense for code that was actually followed
+    let flow =
+        matches.is_present("flow") || matches.is_present("cfg") || matches.is_present("call_graph");
+    let inst_vec = if flow {
+        disassemble_flow(&buffer, start_pc, &entries)
     } else {
-        file.metadata().unwrap().len()
+        disassemble_stage1(&buffer, start_pc)
     };
+    let words = to_words(&buffer).0;
+    let vectors = read_vectors(&words, start_pc);
+    // a linear sweep finds "functions" in the data, so without --flow the
+    // only SUB_ labels are for direct calls
+    let no_labels = matches.is_present("no_labels");
+    let wants_graph = matches.is_present("cfg") || matches.is_present("call_graph") || !no_labels;
+    let graph = (flow && wants_graph).then(|| {
+        CallGraph::new(
+            &inst_vec,
+            &flow_entries(&words, start_pc, &entries),
+            &vectors,
+        )
+    });
+    // the user's names win over generated ones, then the trap handlers'
+    // names, then SUB_ for functions
+    let mut labels = symbol_labels(&symbols);
+    if !no_labels {
+        for (address, label) in vector_labels(&vectors)
+            .into_iter()
+            .chain(graph.iter().flat_map(function_labels))
+            .chain(auto_labels(&words, start_pc, &inst_vec))
+        {
+            labels.entry(address).or_insert(label);
+        }
+    }
 
-    let mut buffer = vec![0u8; buffer_size as usize];
-    file.seek(SeekFrom::Start(offset))
-        .expect("Unable to seek to offset");
-    file.read(buffer.as_mut_slice())
-        .expect("Reading from file failed");
+    let fields = if matches.is_present("fields") {
+        track_fields(&inst_vec, &field_entries(&words, start_pc, &entries))
+    } else {
+        BTreeMap::new()
+    };
 
-    disassemble(buffer.as_mut_slice(), start_pc);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let graph_text = match (&graph, matches.value_of("call_graph")) {
+        (Some(graph), Some("dot")) => Some(graph.to_dot(&labels)),
+        (Some(graph), Some(_)) => Some(graph.to_text(&labels)),
+        (Some(graph), None) if matches.is_present("cfg") => Some(graph.cfg.to_dot(&labels)),
+        _ => None,
+    };
+    if let Some(text) = graph_text {
+        let result = out.write_all(text.as_bytes()).and_then(|()| out.flush());
+        match result {
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            Ok(()) => {}
+        }
+        return;
+    }
+    let listing = Listing {
+        labels,
+        implicit_registers: matches.is_present("implicit"),
+        fields,
+        vectors,
+        kinds: symbol_kinds(&symbols),
+    };
+    let result =
+        try_disassemble_stage2_with(inst_vec, &listing, &mut out).and_then(|()| Ok(out.flush()?));
+    match result {
+        // piped into head or similar
+        Err(Error::Io(e)) if e.kind() == ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        Ok(()) => {}
+    }
 }
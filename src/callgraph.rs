@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::cfg::{Cfg, EdgeKind};
+use crate::disasm::DecodedInstruction;
+use crate::instruction::Instruction;
+use crate::register::Reg;
+use crate::symbol::BitAddress;
+use crate::vectors::Vector;
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub entry: BitAddress,
+    // starts of the blocks in the Cfg it's made of, a tail shared with
+    // another function counts for both
+    pub blocks: BTreeSet<BitAddress>,
+    // from its lowest instruction to the end of its highest one. The blocks
+    // are only what the code reaches from the entry, so this already stops
+    // at whatever really ends the function (a RETS after an MMFM SP, a RETI,
+    // a tail jump) without having to recognise epilogues
+    pub start: BitAddress,
+    pub end: BitAddress,
+    // entries of the functions it calls, traps into or jumps to the top of
+    pub calls: BTreeSet<BitAddress>,
+    pub callers: BTreeSet<BitAddress>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    pub cfg: Cfg,
+    pub functions: BTreeMap<BitAddress, Function>,
+    // CALL Rs with Rs loaded by a MOVI earlier in the block, by the address
+    // of the CALL
+    pub register_calls: BTreeMap<BitAddress, BitAddress>,
+}
+
+fn is_sp(number: u8) -> bool {
+    Reg::from_number(number) == Reg::Sp
+}
+
+// MMTM SP saving registers right after a return or unconditional jump is
+// the start of a function even when nothing visibly calls it
+fn prologues(instructions: &[DecodedInstruction]) -> Vec<BitAddress> {
+    instructions
+        .windows(2)
+        .filter(|pair| pair[0].is_terminator() && pair[0].next_address() == pair[1].address)
+        .filter(|pair| matches!(pair[1].instruction, Instruction::Mmtm(rd, _) if is_sp(rd.0)))
+        .map(|pair| pair[1].address)
+        .collect()
+}
+
+// The target of a CALL Rs that ends a block, when Rs was last set in the
+// same block by a MOVI
+fn register_call(instructions: &[DecodedInstruction]) -> Option<BitAddress> {
+    let (call, before) = instructions.split_last()?;
+    let reg = match call.instruction {
+        Instruction::Call(rs) => Reg::from_number(rs.0),
+        _ => return None,
+    };
+    let setter = before
+        .iter()
+        .rev()
+        .find(|decoded| decoded.instruction.writes().contains(&reg))?;
+    let target = match setter.instruction {
+        Instruction::Movil(il, _) => BitAddress(il.0),
+        Instruction::Moviw(iw, _) => BitAddress(iw.0 as i16 as u32),
+        _ => return None,
+    };
+    target.is_word_aligned().then_some(target)
+}
+
+impl CallGraph {
+    // Functions start at `entries`, at call and trap targets and at MMTM SP
+    // prologues, and take in every block reachable from there without going
+    // through another function's entry
+    pub fn new(
+        instructions: &[DecodedInstruction],
+        entries: &[BitAddress],
+        vectors: &[Vector],
+    ) -> Self {
+        let mut roots: BTreeSet<_> = entries.iter().copied().collect();
+        roots.extend(prologues(instructions));
+        // resolving a CALL through a register can reach more code with more
+        // of them in it
+        let (cfg, register_calls) = loop {
+            let cfg = Cfg::new(
+                instructions,
+                &roots.iter().copied().collect::<Vec<_>>(),
+                vectors,
+            );
+            let register_calls: BTreeMap<_, _> = cfg
+                .blocks
+                .values()
+                .filter_map(|block| {
+                    register_call(&block.instructions).map(|target| (block.last().address, target))
+                })
+                .collect();
+            let before = roots.len();
+            roots.extend(register_calls.values());
+            if roots.len() == before {
+                break (cfg, register_calls);
+            }
+        };
+
+        let mut entries: BTreeSet<_> = roots
+            .into_iter()
+            .filter(|root| cfg.blocks.contains_key(root))
+            .collect();
+        for edge in &cfg.edges {
+            if matches!(edge.kind, EdgeKind::Call | EdgeKind::Trap) {
+                entries.insert(edge.to);
+            }
+        }
+        // reached by a jump, e.g. a tail call
+        for block in cfg.blocks.values() {
+            if let Instruction::Mmtm(rd, _) = block.instructions[0].instruction {
+                if is_sp(rd.0) {
+                    entries.insert(block.start);
+                }
+            }
+        }
+
+        let mut functions = BTreeMap::new();
+        for &entry in &entries {
+            let mut blocks = BTreeSet::new();
+            let mut calls = BTreeSet::new();
+            let mut pending = vec![entry];
+            while let Some(address) = pending.pop() {
+                if !blocks.insert(address) {
+                    continue;
+                }
+                let block = &cfg.blocks[&address];
+                calls.extend(register_calls.get(&block.last().address));
+                for edge in cfg.successors(address) {
+                    match edge.kind {
+                        EdgeKind::Call | EdgeKind::Trap => {
+                            calls.insert(edge.to);
+                        }
+                        _ if entries.contains(&edge.to) && edge.to != entry => {
+                            calls.insert(edge.to);
+                        }
+                        _ => pending.push(edge.to),
+                    }
+                }
+            }
+            let start = *blocks.iter().next().unwrap();
+            let end = blocks
+                .iter()
+                .map(|block| cfg.blocks[block].end())
+                .max()
+                .unwrap();
+            functions.insert(
+                entry,
+                Function {
+                    entry,
+                    blocks,
+                    start,
+                    end,
+                    calls,
+                    callers: BTreeSet::new(),
+                },
+            );
+        }
+        let calls: Vec<_> = functions
+            .values()
+            .flat_map(|function| {
+                function
+                    .calls
+                    .iter()
+                    .map(move |callee| (function.entry, *callee))
+            })
+            .collect();
+        for (caller, callee) in calls {
+            if let Some(function) = functions.get_mut(&callee) {
+                function.callers.insert(caller);
+            }
+        }
+
+        CallGraph {
+            cfg,
+            functions,
+            register_calls,
+        }
+    }
+
+    // One paragraph per function with its extent, what it calls and what
+    // calls it
+    pub fn to_text(&self, labels: &BTreeMap<BitAddress, String>) -> String {
+        let name = |address: &BitAddress| {
+            labels
+                .get(address)
+                .cloned()
+                .unwrap_or_else(|| address.to_string())
+        };
+        let names = |addresses: &BTreeSet<BitAddress>| {
+            let names: Vec<_> = addresses.iter().map(name).collect();
+            names.join(", ")
+        };
+        let mut text = String::new();
+        for function in self.functions.values() {
+            writeln!(
+                text,
+                "{:08X}:\t{}\t{}-{}",
+                function.entry.0,
+                name(&function.entry),
+                function.start,
+                function.end
+            )
+            .unwrap();
+            if !function.calls.is_empty() {
+                writeln!(text, "\tcalls {}", names(&function.calls)).unwrap();
+            }
+            if !function.callers.is_empty() {
+                writeln!(text, "\tcalled by {}", names(&function.callers)).unwrap();
+            }
+        }
+        text
+    }
+
+    pub fn to_dot(&self, labels: &BTreeMap<BitAddress, String>) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph calls {{").unwrap();
+        writeln!(dot, "\tnode [shape=box, fontname=\"monospace\"];").unwrap();
+        for function in self.functions.values() {
+            let name = labels
+                .get(&function.entry)
+                .cloned()
+                .unwrap_or_else(|| function.entry.to_string());
+            writeln!(
+                dot,
+                "\t\"{:08X}\" [label=\"{}\"];",
+                function.entry.0,
+                name.replace('"', "\\\"")
+            )
+            .unwrap();
+        }
+        for function in self.functions.values() {
+            for callee in &function.calls {
+                writeln!(
+                    dot,
+                    "\t\"{:08X}\" -> \"{:08X}\";",
+                    function.entry.0, callee.0
+                )
+                .unwrap();
+            }
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
+
+// SUB_ labels for every function entry, like disasm::auto_labels() gives
+// the targets of direct calls
+pub fn function_labels(graph: &CallGraph) -> BTreeMap<BitAddress, String> {
+    graph
+        .functions
+        .keys()
+        .map(|entry| (*entry, format!("SUB_{:08X}", entry.0)))
+        .collect()
+}
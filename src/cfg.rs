@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::disasm::{labeled_text, DecodedInstruction};
+use crate::instruction::Instruction;
+use crate::symbol::BitAddress;
+use crate::vectors::Vector;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    // on to the next instruction, including coming back from a call or trap
+    Fallthrough,
+    // a jump, taken branch or DSJ loop
+    Branch,
+    Call,
+    // into the handler from the vector table
+    Trap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: BitAddress,
+    pub to: BitAddress,
+    pub kind: EdgeKind,
+}
+
+// A run of instructions only entered at the top and only left at the bottom
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub start: BitAddress,
+    pub instructions: Vec<DecodedInstruction>,
+}
+
+impl BasicBlock {
+    pub fn last(&self) -> &DecodedInstruction {
+        self.instructions.last().unwrap()
+    }
+
+    // the address after the last instruction
+    pub fn end(&self) -> BitAddress {
+        self.last().next_address()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Cfg {
+    pub blocks: BTreeMap<BitAddress, BasicBlock>,
+    // sorted by the block they leave
+    pub edges: Vec<Edge>,
+}
+
+// Where execution can go after `decoded`, other than through a register
+fn successors(decoded: &DecodedInstruction, vectors: &[Vector]) -> Vec<(BitAddress, EdgeKind)> {
+    let mut successors = vec![];
+    if let Some(target) = decoded.branch_target() {
+        let kind = if decoded.is_call() {
+            EdgeKind::Call
+        } else {
+            EdgeKind::Branch
+        };
+        successors.push((target, kind));
+    }
+    if let Instruction::Trap(n) = decoded.instruction {
+        successors.extend(
+            vectors
+                .iter()
+                .filter(|vector| vector.trap == n.0)
+                .map(|vector| (vector.handler, EdgeKind::Trap)),
+        );
+    }
+    if !decoded.is_terminator() {
+        successors.push((decoded.next_address(), EdgeKind::Fallthrough));
+    }
+    successors
+}
+
+// Anything that can go somewhere other than the next instruction ends a block
+fn ends_block(instruction: &Instruction) -> bool {
+    instruction.branch_target().is_some()
+        || instruction.is_terminator()
+        || matches!(instruction, Instruction::Trap(_) | Instruction::Call(_))
+}
+
+impl Cfg {
+    // Splits what can be reached from `entries` (following calls and traps
+    // through `vectors`) into basic blocks. Jumps through a register and
+    // anything that isn't in `instructions` are dead ends.
+    pub fn new(
+        instructions: &[DecodedInstruction],
+        entries: &[BitAddress],
+        vectors: &[Vector],
+    ) -> Self {
+        let by_address: BTreeMap<_, _> = instructions
+            .iter()
+            .filter(|decoded| !matches!(decoded.instruction, Instruction::Dw(_)))
+            .map(|decoded| (decoded.address, decoded))
+            .collect();
+
+        // everything reachable, and the instructions that start a block
+        let mut reached = BTreeSet::new();
+        let mut leaders: BTreeSet<_> = entries.iter().copied().collect();
+        let mut pending = entries.to_vec();
+        while let Some(address) = pending.pop() {
+            let decoded = match by_address.get(&address) {
+                Some(decoded) if reached.insert(address) => decoded,
+                _ => continue,
+            };
+            for (target, kind) in successors(decoded, vectors) {
+                if kind != EdgeKind::Fallthrough || ends_block(&decoded.instruction) {
+                    leaders.insert(target);
+                }
+                pending.push(target);
+            }
+        }
+
+        let mut cfg = Cfg::default();
+        for &leader in &leaders {
+            if !reached.contains(&leader) {
+                continue;
+            }
+            let mut block = BasicBlock {
+                start: leader,
+                instructions: vec![],
+            };
+            let mut address = leader;
+            loop {
+                let decoded = by_address[&address];
+                block.instructions.push(*decoded);
+                address = decoded.next_address();
+                if ends_block(&decoded.instruction)
+                    || leaders.contains(&address)
+                    || !reached.contains(&address)
+                {
+                    break;
+                }
+            }
+            for (to, kind) in successors(block.last(), vectors) {
+                if reached.contains(&to) {
+                    cfg.edges.push(Edge {
+                        from: leader,
+                        to,
+                        kind,
+                    });
+                }
+            }
+            cfg.blocks.insert(leader, block);
+        }
+        cfg
+    }
+
+    pub fn successors(&self, block: BitAddress) -> impl Iterator<Item = &Edge> {
+        // the edges go in block order
+        let first = self.edges.partition_point(|edge| edge.from < block);
+        self.edges[first..]
+            .iter()
+            .take_while(move |edge| edge.from == block)
+    }
+
+    pub fn predecessors(&self, block: BitAddress) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+
+    // A Graphviz digraph with each block's instructions in its box, labels
+    // in place of addresses where there are any
+    pub fn to_dot(&self, labels: &BTreeMap<BitAddress, String>) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph cfg {{").unwrap();
+        writeln!(dot, "\tnode [shape=box, fontname=\"monospace\"];").unwrap();
+        for block in self.blocks.values() {
+            let mut text = String::new();
+            if let Some(label) = labels.get(&block.start) {
+                write!(text, "{}:\\l", label).unwrap();
+            }
+            for decoded in &block.instructions {
+                let (instruction, _) = labeled_text(&decoded.instruction, labels);
+                write!(text, "{:08X}: {}\\l", decoded.address.0, instruction).unwrap();
+            }
+            writeln!(
+                dot,
+                "\t\"{:08X}\" [label=\"{}\"];",
+                block.start.0,
+                text.replace('"', "\\\"")
+            )
+            .unwrap();
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Branch => " [color=blue]",
+                EdgeKind::Call => " [style=dashed, label=\"call\"]",
+                EdgeKind::Trap => " [style=dashed, label=\"trap\"]",
+            };
+            writeln!(
+                dot,
+                "\t\"{:08X}\" -> \"{:08X}\"{};",
+                edge.from.0, edge.to.0, style
+            )
+            .unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
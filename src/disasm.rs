@@ -1,1290 +1,985 @@
 use bitvec::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
 
+use crate::error::Error;
+use crate::fields::FieldState;
 use crate::instruction::Instruction;
+use crate::io_registers::io_register_labels;
+use crate::register::Reg;
+use crate::symbol_file::SymbolKind;
+use crate::vectors::{hardware_trap_name, read_vectors, vector_labels, Vector};
 use core::fmt::{self, Formatter, Write};
 
 use crate::symbol::{
-    Address, Condition, Offset, Offset8, Rd, RegList, Rs, D, F, FE, FS, IL, IW, K, N, PC, Z,
+    Address, BitAddress, Condition, Offset, Offset8, Rd, RegList, Rs, D, F, FE, FS, IL, IW, K, N,
+    PC, Z,
 };
 
-pub fn disassemble_stage1(
+// An instruction along with where it was found
+#[derive(Debug, Clone, Copy)]
+pub struct DecodedInstruction {
+    // what the listing shows
+    pub address: BitAddress,
+    // word index, address / 16
+    pub pc: usize,
+    pub instruction: Instruction,
+    // in bits, 16 per word apart from a lone byte at the end of the input
+    pub size: u32,
+    // the longest instruction, MOVE @abs, @abs, is 5 words
+    raw: [u16; 5],
+}
+
+impl DecodedInstruction {
+    pub fn new(address: BitAddress, instruction: Instruction, words: &[u16]) -> Self {
+        let mut raw = [0; 5];
+        raw[..words.len()].copy_from_slice(words);
+        DecodedInstruction {
+            address,
+            pc: address.word(),
+            instruction,
+            size: words.len() as u32 * 16,
+            raw,
+        }
+    }
+
+    // the instruction's words as they were in memory
+    pub fn words(&self) -> &[u16] {
+        &self.raw[..(self.size as usize).div_ceil(16)]
+    }
+
+    // the don't-care bits that were set in the first word, which assembling
+    // the instruction would clear
+    pub fn dont_care_bits(&self) -> u16 {
+        self.words()
+            .first()
+            .map_or(0, |word| word ^ self.instruction.encoding()[0])
+    }
+
+    // address of the instruction after this one
+    pub fn next_address(&self) -> BitAddress {
+        self.address.offset_bits(self.size as i64)
+    }
+
+    pub fn branch_target(&self) -> Option<BitAddress> {
+        self.instruction.branch_target()
+    }
+
+    pub fn is_terminator(&self) -> bool {
+        self.instruction.is_terminator()
+    }
+
+    pub fn is_call(&self) -> bool {
+        self.instruction.is_call()
+    }
+}
+
+// Anything that isn't an instruction comes out as DW, one word at a time
+pub fn disassemble_stage1(bytebuf: &[u8], start: BitAddress) -> Vec<DecodedInstruction> {
+    let (words, odd_byte) = to_words(bytebuf);
+    let mut inst_vec: Vec<_> = Decoder::new(&words, start).collect();
+    if let Some(byte) = odd_byte {
+        inst_vec.push(trailing_byte(start.offset_words(words.len() as i64), byte));
+    }
+    inst_vec
+}
+
+// an odd byte at the very end, there's nothing to decode
+pub(crate) fn trailing_byte(address: BitAddress, byte: u8) -> DecodedInstruction {
+    let mut decoded =
+        DecodedInstruction::new(address, Instruction::Dw(IW(byte as u16)), &[byte as u16]);
+    decoded.size = 8;
+    decoded
+}
+
+// Same as disassemble_stage1() but for input that should be all code, the
+// first word that would have been DW is an error instead
+pub fn try_disassemble_stage1(
     bytebuf: &[u8],
-    start_addr: usize,
-) -> Vec<(usize, Instruction, Vec<u16>)> {
+    start: BitAddress,
+) -> Result<Vec<DecodedInstruction>, Error> {
+    let (words, odd_byte) = to_words(bytebuf);
     let mut inst_vec = vec![];
-    let bitslice = bytebuf.view_bits::<Lsb0>();
-    let mut word_iter = bitslice.chunks(16).enumerate();
-    loop {
-        match word_iter.next() {
-            Some((pc, word)) => {
-                let mut bad = false;
-                let upper7 = word.get(9..=15).unwrap().load::<u8>();
-                let subop = word.get(5..=8).unwrap().load::<u8>();
-                let rs = word.get(5..=8).unwrap().load::<u8>();
-                let rf = *word.get(4).unwrap();
-                let rd = word.get(0..=3).unwrap().load::<u8>();
-                let f = *word.get(9).unwrap();
-                let fs = word.get(0..=4).unwrap().load::<u8>();
-                let n = word.get(0..=4).unwrap().load::<u8>();
-                let fe = *word.get(5).unwrap();
-                let k = word.get(5..=9).unwrap().load::<u8>();
-                let d = *word.get(10).unwrap();
-                let z = *word.get(7).unwrap();
-                let cc = word.get(8..=11).unwrap().load::<u8>();
-                match upper7 {
-                    0b0000000 => {
-                        match subop {
-                            1 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Rev(Rd((rf as u8) << 4 | rd)),
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            8 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Emu,
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            9 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Exgpc(Rd((rf as u8) << 4 | rd), F(f)),
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            10 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Getpc(Rd((rf as u8) << 4 | rd)),
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            11 => {
-                                // this is kind of strange that we assign rd to Rs, but it's in the
-                                // position of rd, and the manual calls it Rs because it's the
-                                // source of the argument to jump to
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Jump(Rs((rf as u8) << 4 | rd)),
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            12 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Getst(Rd((rf as u8) << 4 | rd)),
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            13 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Putst(Rs((rf as u8) << 4 | rd)),
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            14 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Popst,
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            15 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Pushst,
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            _ => {
-                                bad = true;
-                            }
-                        }
+    let mut pos = 0;
+    while pos < words.len() {
+        let address = start.offset_words(pos as i64);
+        let (inst, len) = Decoder::try_decode_one(&words[pos..], address)?;
+        inst_vec.push(DecodedInstruction::new(
+            address,
+            inst,
+            &words[pos..pos + len],
+        ));
+        pos += len;
+    }
+    if odd_byte.is_some() {
+        return Err(Error::Truncated {
+            address: start.offset_words(words.len() as i64),
+        });
+    }
+    Ok(inst_vec)
+}
+
+// little endian words, plus the last byte if there's an odd number of them
+pub fn to_words(bytebuf: &[u8]) -> (Vec<u16>, Option<u8>) {
+    let chunks = bytebuf.chunks_exact(2);
+    let odd_byte = chunks.remainder().first().copied();
+    let words = chunks
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    (words, odd_byte)
+}
+
+// Decodes instructions one at a time out of a buffer of words, without
+// allocating for each one
+#[derive(Debug, Clone)]
+pub struct Decoder<'a> {
+    words: &'a [u16],
+    // address of words[0]
+    start: BitAddress,
+    // index into words of the next instruction
+    pos: usize,
+    // the buffer ended partway through an instruction, so the rest is data
+    truncated: bool,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(words: &'a [u16], start: BitAddress) -> Self {
+        Decoder {
+            words,
+            start,
+            pos: 0,
+            truncated: false,
+        }
+    }
+
+    // The instruction at the start of `words`, which sits at `address`, and
+    // how many words it takes up. Don't-care bits are ignored, like the chip
+    // does. Anything else that isn't an instruction, including one cut off by
+    // the end of `words`, is a one word DW (and an empty slice is a zero
+    // length one).
+    pub fn decode_one(words: &[u16], address: BitAddress) -> (Instruction, usize) {
+        match Self::decode(words, address) {
+            Ok(decoded) => decoded,
+            Err(_) => (
+                Instruction::Dw(IW(words.first().copied().unwrap_or(0))),
+                words.len().min(1),
+            ),
+        }
+    }
+
+    // Same as decode_one() but says why the words aren't an instruction, and
+    // is strict about it: an instruction with don't-care bits set is an
+    // InvalidEncoding, since it wouldn't assemble back to the same words
+    pub fn try_decode_one(
+        words: &[u16],
+        address: BitAddress,
+    ) -> Result<(Instruction, usize), Error> {
+        let (inst, len) = Self::decode(words, address)?;
+        if *inst.encoding() != words[..len] {
+            return Err(Error::InvalidEncoding {
+                address,
+                word: words[0],
+            });
+        }
+        Ok((inst, len))
+    }
+
+    // decode_one() with the reason the words aren't an instruction
+    pub(crate) fn decode(
+        words: &[u16],
+        address: BitAddress,
+    ) -> Result<(Instruction, usize), Error> {
+        let first = match words.first() {
+            Some(first) => *first,
+            None => return Err(Error::Truncated { address }),
+        };
+        let word = first.view_bits::<Lsb0>();
+        let mut word_iter = words[1..].iter();
+        let mut inst = Instruction::Dw(IW(first));
+        let mut bad = false;
+        let mut truncated = false;
+        let upper7 = word.get(9..=15).unwrap().load::<u8>();
+        let subop = word.get(5..=8).unwrap().load::<u8>();
+        let rs = word.get(5..=8).unwrap().load::<u8>();
+        let rf = *word.get(4).unwrap();
+        let rd = word.get(0..=3).unwrap().load::<u8>();
+        let f = *word.get(9).unwrap();
+        let fs = word.get(0..=4).unwrap().load::<u8>();
+        let n = word.get(0..=4).unwrap().load::<u8>();
+        let fe = *word.get(5).unwrap();
+        let k = word.get(5..=9).unwrap().load::<u8>();
+        let d = *word.get(10).unwrap();
+        let z = *word.get(7).unwrap();
+        let cc = word.get(8..=11).unwrap().load::<u8>();
+        match upper7 {
+            0b0000000 => {
+                match subop {
+                    1 => {
+                        inst = Instruction::Rev(Rd((rf as u8) << 4 | rd));
+                    }
+                    8 => {
+                        inst = Instruction::Emu;
+                    }
+                    9 => {
+                        inst = Instruction::Exgpc(Rd((rf as u8) << 4 | rd), F(f));
+                    }
+                    10 => {
+                        inst = Instruction::Getpc(Rd((rf as u8) << 4 | rd));
+                    }
+                    11 => {
+                        // this is kind of strange that we assign rd to Rs, but it's in the
+                        // position of rd, and the manual calls it Rs because it's the
+                        // source of the argument to jump to
+                        inst = Instruction::Jump(Rs((rf as u8) << 4 | rd));
+                    }
+                    12 => {
+                        inst = Instruction::Getst(Rd((rf as u8) << 4 | rd));
+                    }
+                    13 => {
+                        inst = Instruction::Putst(Rs((rf as u8) << 4 | rd));
+                    }
+                    14 => {
+                        inst = Instruction::Popst;
+                    }
+                    15 => {
+                        inst = Instruction::Pushst;
                     }
-                    0b0000001 => match subop {
-                        8 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Nop,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        9 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Clrc,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        10 => {
-                            let mut src_addr: u32 = 0;
-                            let mut dst_addr: u32 = 0;
-                            let src_lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let src_msb = word_iter.next().unwrap().1.load::<u16>();
-                            let dst_lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let dst_msb = word_iter.next().unwrap().1.load::<u16>();
-                            src_addr.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(src_lsb);
-                            src_addr.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(src_msb);
-                            dst_addr.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(dst_lsb);
-                            dst_addr.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(dst_msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::MovbAbsoluteToAbsolute(
-                                    Address(src_addr),
-                                    Address(dst_addr),
-                                ),
-                                vec![word.load::<u16>(), src_lsb, src_msb, dst_lsb, dst_msb],
-                            ));
-                        }
-                        11 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Dint,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        12 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Abs(Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        13 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Neg(Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        14 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Negb(Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        15 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Not(Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        _ => {
-                            bad = true;
-                        }
-                    },
-                    0b0000010 | 0b0000011 => {
-                        match subop {
-                            8 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Sext(Rd((rf as u8) << 4 | rd), F(f)),
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            9 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Zext(Rd((rf as u8) << 4 | rd), F(f)),
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            10 | 11 => {
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::Setf(FS(fs), FE(fe), F(f)),
-                                    vec![word.load::<u16>()],
-                                ));
-                            }
-                            12 => {
-                                let mut address: u32 = 0;
-                                let lsb = word_iter.next().unwrap().1.load::<u16>();
-                                let msb = word_iter.next().unwrap().1.load::<u16>();
-                                address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                                address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::MoveFieldRegToAbsolute(
-                                        Rs((rf as u8) << 4 | rd),
-                                        Address(address),
-                                        F(f),
-                                    ),
-                                    vec![word.load::<u16>(), lsb, msb],
-                                ));
-                            }
-                            13 => {
-                                let mut address: u32 = 0;
-                                let lsb = word_iter.next().unwrap().1.load::<u16>();
-                                let msb = word_iter.next().unwrap().1.load::<u16>();
-                                address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                                address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::MoveFieldAbsoluteToReg(
-                                        Address(address),
-                                        Rd((rf as u8) << 4 | rd),
-                                        F(f),
-                                    ),
-                                    vec![word.load::<u16>(), lsb, msb],
-                                ));
-                            }
-                            14 => {
-                                let mut src_addr: u32 = 0;
-                                let mut dst_addr: u32 = 0;
-                                let src_lsb = word_iter.next().unwrap().1.load::<u16>();
-                                let src_msb = word_iter.next().unwrap().1.load::<u16>();
-                                let dst_lsb = word_iter.next().unwrap().1.load::<u16>();
-                                let dst_msb = word_iter.next().unwrap().1.load::<u16>();
-                                src_addr.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(src_lsb);
-                                src_addr.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(src_msb);
-                                dst_addr.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(dst_lsb);
-                                dst_addr.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(dst_msb);
-                                inst_vec.push((
-                                    pc + start_addr,
-                                    Instruction::MoveFieldAbsoluteToAbsolute(
-                                        Address(src_addr),
-                                        Address(dst_addr),
-                                        F(f),
-                                    ),
-                                    vec![word.load::<u16>(), src_lsb, src_msb, dst_lsb, dst_msb],
-                                ));
-                            }
-                            15 => {
-                                if f {
-                                    // this has nothing to do with fields I was just too lazy to
-                                    // make an alias for bit 9
-                                    let mut address: u32 = 0;
-                                    let lsb = word_iter.next().unwrap().1.load::<u16>();
-                                    let msb = word_iter.next().unwrap().1.load::<u16>();
-                                    address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                                    address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                                    inst_vec.push((
-                                        pc + start_addr,
-                                        Instruction::MovbAbsoluteToReg(
-                                            Address(address),
-                                            Rd((rf as u8) << 4 | rd),
-                                        ),
-                                        vec![word.load::<u16>(), lsb, msb],
-                                    ));
-                                } else {
-                                    let mut address: u32 = 0;
-                                    let lsb = word_iter.next().unwrap().1.load::<u16>();
-                                    let msb = word_iter.next().unwrap().1.load::<u16>();
-                                    address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                                    address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                                    inst_vec.push((
-                                        pc + start_addr,
-                                        Instruction::MovbRegToAbsolute(
-                                            Rs((rf as u8) << 4 | rd),
-                                            Address(address),
-                                        ),
-                                        vec![word.load::<u16>(), lsb, msb],
-                                    ));
-                                }
-                            }
-                            _ => {
-                                bad = true;
-                            }
-                        }
+                    _ => {
+                        bad = true;
                     }
-                    0b0000100 => match subop {
-                        8 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Trap(N(n)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        9 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Call(Rs(rd)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        10 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Reti,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        11 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Rets(N(n)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        12 => {
-                            let reglist = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Mmtm(Rd((rf as u8) << 4 | rd), RegList(reglist)),
-                                vec![word.load::<u16>(), reglist],
-                            ));
-                        }
-                        13 => {
-                            let reglist = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Mmfm(Rs((rf as u8) << 4 | rd), RegList(reglist)),
-                                vec![word.load::<u16>(), reglist],
-                            ));
-                        }
-                        14 => {
-                            let iw = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Moviw(IW(iw), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), iw],
-                            ));
-                        }
-                        15 => {
-                            let mut il: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
-                            il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                            il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Movil(IL(il), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        }
-                        _ => {
-                            bad = true;
-                        }
-                    },
-                    0b0000101 => match subop {
-                        8 => {
-                            let iw = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Addiw(IW(iw), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), iw],
-                            ));
-                        }
-                        9 => {
-                            let mut il: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
-                            il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                            il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Addil(IL(il), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        }
-                        10 => {
-                            let iw = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Cmpiw(IW(iw), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), iw],
-                            ));
-                        }
-                        11 => {
-                            let mut il: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
-                            il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                            il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Cmpil(IL(il), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        }
-                        12 => {
-                            let mut il: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
-                            il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                            il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Andi(IL(il), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        }
-                        13 => {
-                            let mut il: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
-                            il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                            il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Ori(IL(il), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        }
-                        14 => {
-                            let mut il: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
-                            il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                            il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Xori(IL(il), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        }
-                        15 => {
-                            let iw = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Subiw(IW(iw), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), iw],
-                            ));
-                        }
-                        _ => {
-                            bad = true;
-                        }
-                    },
-                    0b0000110 => match subop {
-                        8 => {
-                            let mut il: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
-                            il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                            il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Subil(IL(il), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        }
-                        9 => {
-                            let offset = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Callr(
-                                    Offset(offset),
-                                    PC(pc as u32 + start_addr as u32),
-                                ),
-                                vec![word.load::<u16>(), offset],
-                            ));
-                        }
-                        10 => {
+                }
+            }
+            0b0000001 => match subop {
+                8 => {
+                    inst = Instruction::Nop;
+                }
+                9 => {
+                    inst = Instruction::Clrc;
+                }
+                10 => {
+                    let mut src_addr: u32 = 0;
+                    let mut dst_addr: u32 = 0;
+                    let src_lsb = next_word(&mut word_iter, &mut truncated);
+                    let src_msb = next_word(&mut word_iter, &mut truncated);
+                    let dst_lsb = next_word(&mut word_iter, &mut truncated);
+                    let dst_msb = next_word(&mut word_iter, &mut truncated);
+                    src_addr.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(src_lsb);
+                    src_addr.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(src_msb);
+                    dst_addr.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(dst_lsb);
+                    dst_addr.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(dst_msb);
+                    inst =
+                        Instruction::MovbAbsoluteToAbsolute(Address(src_addr), Address(dst_addr));
+                }
+                11 => {
+                    inst = Instruction::Dint;
+                }
+                12 => {
+                    inst = Instruction::Abs(Rd((rf as u8) << 4 | rd));
+                }
+                13 => {
+                    inst = Instruction::Neg(Rd((rf as u8) << 4 | rd));
+                }
+                14 => {
+                    inst = Instruction::Negb(Rd((rf as u8) << 4 | rd));
+                }
+                15 => {
+                    inst = Instruction::Not(Rd((rf as u8) << 4 | rd));
+                }
+                _ => {
+                    bad = true;
+                }
+            },
+            0b0000010 | 0b0000011 => {
+                match subop {
+                    8 => {
+                        inst = Instruction::Sext(Rd((rf as u8) << 4 | rd), F(f));
+                    }
+                    9 => {
+                        inst = Instruction::Zext(Rd((rf as u8) << 4 | rd), F(f));
+                    }
+                    10 | 11 => {
+                        inst = Instruction::Setf(FS(fs), FE(fe), F(f));
+                    }
+                    12 => {
+                        let mut address: u32 = 0;
+                        let lsb = next_word(&mut word_iter, &mut truncated);
+                        let msb = next_word(&mut word_iter, &mut truncated);
+                        address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                        address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                        inst = Instruction::MoveFieldRegToAbsolute(
+                            Rs((rf as u8) << 4 | rd),
+                            Address(address),
+                            F(f),
+                        );
+                    }
+                    13 => {
+                        let mut address: u32 = 0;
+                        let lsb = next_word(&mut word_iter, &mut truncated);
+                        let msb = next_word(&mut word_iter, &mut truncated);
+                        address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                        address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                        inst = Instruction::MoveFieldAbsoluteToReg(
+                            Address(address),
+                            Rd((rf as u8) << 4 | rd),
+                            F(f),
+                        );
+                    }
+                    14 => {
+                        let mut src_addr: u32 = 0;
+                        let mut dst_addr: u32 = 0;
+                        let src_lsb = next_word(&mut word_iter, &mut truncated);
+                        let src_msb = next_word(&mut word_iter, &mut truncated);
+                        let dst_lsb = next_word(&mut word_iter, &mut truncated);
+                        let dst_msb = next_word(&mut word_iter, &mut truncated);
+                        src_addr.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(src_lsb);
+                        src_addr.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(src_msb);
+                        dst_addr.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(dst_lsb);
+                        dst_addr.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(dst_msb);
+                        inst = Instruction::MoveFieldAbsoluteToAbsolute(
+                            Address(src_addr),
+                            Address(dst_addr),
+                            F(f),
+                        );
+                    }
+                    15 => {
+                        if f {
+                            // this has nothing to do with fields I was just too lazy to
+                            // make an alias for bit 9
                             let mut address: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
+                            let lsb = next_word(&mut word_iter, &mut truncated);
+                            let msb = next_word(&mut word_iter, &mut truncated);
                             address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
                             address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Calla(Address(address)),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        }
-                        11 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Eint,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        12 => {
-                            let offset = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Dsj(Rd((rf as u8) << 4 | rd), Offset(offset)),
-                                vec![word.load::<u16>(), offset],
-                            ));
-                        }
-                        13 => {
-                            let offset = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Dsjeq(Rd((rf as u8) << 4 | rd), Offset(offset)),
-                                vec![word.load::<u16>(), offset],
-                            ));
-                        }
-                        14 => {
-                            let offset = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Dsjne(Rd((rf as u8) << 4 | rd), Offset(offset)),
-                                vec![word.load::<u16>(), offset],
-                            ));
-                        }
-                        15 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Setc,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        _ => {
-                            bad = true;
-                        }
-                    },
-                    0b0000111 => match subop {
-                        8 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Pixbltll,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        9 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Pixbltlxy,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        10 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Pixbltxyl,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        11 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Pixbltxyxy,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        12 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Pixbltbl,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        13 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Pixbltbxy,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        14 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Filll,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        15 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Fillxy,
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        _ => {
-                            bad = true;
-                        }
-                    },
-                    0b0001000 | 0b0001001 => {
-                        if k == 1 {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Inc(Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>()],
-                            ));
-                        } else {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Addk(K(k), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                    }
-                    0b0001010 | 0b0001011 => {
-                        if k == 1 {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Dec(Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>()],
-                            ));
-                        } else {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Subk(K(k), Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                    }
-                    0b0001100 | 0b0001101 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Movk(K(k), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0001110 | 0b0001111 => {
-                        // reminder to deal with 1's complement when formatting and assembling
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Btstk(K(k), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0010000 | 0b0010001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Slak(K(k), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0010010 | 0b0010011 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Sllk(K(k), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0010100 | 0b0010101 => {
-                        // reminder to deal with 2's complement when formatting and assembling
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Srak(K(k), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0010110 | 0b0010111 => {
-                        // reminder to deal with 2's complement when formatting and assembling
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Srlk(K(k), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0011000 | 0b0011001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Rlk(K(k), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0011100..=0b0011111 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Dsjs(
-                                D(d),
+                            inst = Instruction::MovbAbsoluteToReg(
+                                Address(address),
                                 Rd((rf as u8) << 4 | rd),
-                                K(k),
-                                PC(pc as u32 + start_addr as u32),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0100000 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Add(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0100001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Addc(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0100010 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Sub(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0100011 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Subb(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0100100 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Cmp(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0100101 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Btst(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0100110 | 0b0100111 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveReg(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd(if f == false { (rf as u8) << 4 } else { 0 } | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0101000 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::And(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0101001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Andn(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0101010 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Or(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0101011 => {
-                        if rs == rd {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Clr(Rd((rf as u8) << 4 | rd)),
-                                vec![word.load::<u16>()],
-                            ));
+                            );
                         } else {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Xor(
-                                    Rs((rf as u8) << 4 | rs),
-                                    Rd((rf as u8) << 4 | rd),
-                                ),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                    }
-                    0b0101100 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Divs(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0101101 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Divu(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0101110 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Mpys(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0101111 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Mpyu(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0110000 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Sla(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0110001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Sll(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0110010 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Sra(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0110011 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Srl(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0110100 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Rl(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0110101 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Lmo(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0110110 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Mods(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b0110111 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Modu(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1000000 | 0b1000001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldRegToIndirect(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1000010 | 0b1000011 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldIndirectToReg(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1000100 | 0b1000101 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldIndirectToIndirect(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1000110 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MovbRegToIndirect(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1000111 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MovbIndirectToReg(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1001000 | 0b1001001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldRegToIndirectPostinc(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1001010 | 0b1001011 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldIndirectPostincToReg(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1001100 | 0b1001101 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldIndirectToIndirectPostinc(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1001110 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MovbIndirectToIndirect(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1010000 | 0b1010001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldRegToIndirectPredec(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1010010 | 0b1010011 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldIndirectPredecToReg(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1010100 | 0b1010101 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldIndirectToIndirectPredec(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1010110 => {
-                        let offset = word_iter.next().unwrap().1.load::<u16>();
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MovbRegToIndirectOffset(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                Offset(offset),
-                            ),
-                            vec![word.load::<u16>(), offset],
-                        ));
-                    }
-                    0b1010111 => {
-                        let offset = word_iter.next().unwrap().1.load::<u16>();
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MovbIndirectOffsetToReg(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                Offset(offset),
-                            ),
-                            vec![word.load::<u16>(), offset],
-                        ));
-                    }
-                    0b1011000 | 0b1011001 => {
-                        let offset = word_iter.next().unwrap().1.load::<u16>();
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldRegToIndirectOffset(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                                Offset(offset),
-                            ),
-                            vec![word.load::<u16>(), offset],
-                        ));
-                    }
-                    0b1011010 | 0b1011011 => {
-                        let offset = word_iter.next().unwrap().1.load::<u16>();
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldIndirectOffsetToReg(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                                Offset(offset),
-                            ),
-                            vec![word.load::<u16>(), offset],
-                        ));
-                    }
-                    0b1011100 | 0b1011101 => {
-                        let src_offset = word_iter.next().unwrap().1.load::<u16>();
-                        let dst_offset = word_iter.next().unwrap().1.load::<u16>();
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldIndirectOffsetToIndirectOffset(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                                Offset(src_offset),
-                                Offset(dst_offset),
-                            ),
-                            vec![word.load::<u16>(), src_offset, dst_offset],
-                        ));
-                    }
-                    0b1011110 => {
-                        let src_offset = word_iter.next().unwrap().1.load::<u16>();
-                        let dst_offset = word_iter.next().unwrap().1.load::<u16>();
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MovbIndirectOffsetToIndirectOffset(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                Offset(src_offset),
-                                Offset(dst_offset),
-                            ),
-                            vec![word.load::<u16>(), src_offset, dst_offset],
-                        ));
-                    }
-                    0b1100000..=0b1100111 => {
-                        let lower8 = word.get(0..=7).unwrap().load::<u8>();
-                        if lower8 == 0x80 {
                             let mut address: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
+                            let lsb = next_word(&mut word_iter, &mut truncated);
+                            let msb = next_word(&mut word_iter, &mut truncated);
                             address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
                             address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Ja(Condition(cc), Address(address)),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        } else if lower8 == 0x00 {
-                            let offset = word_iter.next().unwrap().1.load::<u16>();
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Jr(
-                                    Condition(cc),
-                                    Offset(offset),
-                                    PC(pc as u32 + start_addr as u32),
-                                ),
-                                vec![word.load::<u16>(), offset],
-                            ));
-                        } else {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Jrs(
-                                    Condition(cc),
-                                    Offset8(lower8),
-                                    PC((pc + start_addr) as u32),
-                                ),
-                                vec![word.load::<u16>()],
-                            ));
+                            inst = Instruction::MovbRegToAbsolute(
+                                Rs((rf as u8) << 4 | rd),
+                                Address(address),
+                            );
                         }
                     }
-                    0b1101000 | 0b1101001 => {
-                        let offset = word_iter.next().unwrap().1.load::<u16>();
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::MoveFieldIndirectOffsetToIndirectPostinc(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                                F(f),
-                                Offset(offset),
-                            ),
-                            vec![word.load::<u16>(), offset],
-                        ));
-                    }
-                    0b1101010 | 0b1101011 => match subop {
-                        0 => {
-                            let mut address: u32 = 0;
-                            let lsb = word_iter.next().unwrap().1.load::<u16>();
-                            let msb = word_iter.next().unwrap().1.load::<u16>();
-                            address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
-                            address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::MoveFieldAbsoluteToIndirectPostinc(
-                                    Address(address),
-                                    Rd((rf as u8) << 4 | rd),
-                                    F(f),
-                                ),
-                                vec![word.load::<u16>(), lsb, msb],
-                            ));
-                        }
-                        8 => {
-                            inst_vec.push((
-                                pc + start_addr,
-                                Instruction::Exgf(Rd((rf as u8) << 4 | rd), F(f)),
-                                vec![word.load::<u16>()],
-                            ));
-                        }
-                        _ => {
-                            bad = true;
-                        }
-                    },
-                    0b1101111 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Line(Z(z)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1110000 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Addxy(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1110001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Subxy(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1110010 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Cmpxy(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1110011 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Cpw(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1110100 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Cvxyl(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1110110 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Movx(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1110111 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Movy(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1111000 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::PixtRegToIndirectxy(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1111001 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::PixtIndirectxyToReg(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1111010 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::PixtIndirectxyToIndirectxy(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1111011 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::Drav(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd)),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1111100 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::PixtRegToIndirect(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1111101 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::PixtIndirectToReg(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
-                    0b1111110 => {
-                        inst_vec.push((
-                            pc + start_addr,
-                            Instruction::PixtIndirectToIndirect(
-                                Rs((rf as u8) << 4 | rs),
-                                Rd((rf as u8) << 4 | rd),
-                            ),
-                            vec![word.load::<u16>()],
-                        ));
-                    }
                     _ => {
                         bad = true;
                     }
                 }
-                if bad == true {
-                    inst_vec.push((
-                        pc + start_addr,
-                        Instruction::Dw(IW(word.load::<u16>())),
-                        vec![word.load::<u16>()],
-                    ));
+            }
+            0b0000100 => match subop {
+                8 => {
+                    inst = Instruction::Trap(N(n));
+                }
+                9 => {
+                    inst = Instruction::Call(Rs((rf as u8) << 4 | rd));
+                }
+                10 => {
+                    inst = Instruction::Reti;
+                }
+                11 => {
+                    inst = Instruction::Rets(N(n));
+                }
+                12 => {
+                    let reglist = next_word(&mut word_iter, &mut truncated);
+                    inst = Instruction::Mmtm(Rd((rf as u8) << 4 | rd), RegList(reglist));
+                }
+                13 => {
+                    let reglist = next_word(&mut word_iter, &mut truncated);
+                    inst = Instruction::Mmfm(Rs((rf as u8) << 4 | rd), RegList(reglist));
+                }
+                14 => {
+                    let iw = next_word(&mut word_iter, &mut truncated);
+                    inst = Instruction::Moviw(IW(iw), Rd((rf as u8) << 4 | rd));
+                }
+                15 => {
+                    let mut il: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::Movil(IL(il), Rd((rf as u8) << 4 | rd));
+                }
+                _ => {
+                    bad = true;
+                }
+            },
+            0b0000101 => match subop {
+                8 => {
+                    let iw = next_word(&mut word_iter, &mut truncated);
+                    inst = Instruction::Addiw(IW(iw), Rd((rf as u8) << 4 | rd));
+                }
+                9 => {
+                    let mut il: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::Addil(IL(il), Rd((rf as u8) << 4 | rd));
+                }
+                10 => {
+                    let iw = next_word(&mut word_iter, &mut truncated);
+                    inst = Instruction::Cmpiw(IW(iw), Rd((rf as u8) << 4 | rd));
+                }
+                11 => {
+                    let mut il: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::Cmpil(IL(il), Rd((rf as u8) << 4 | rd));
+                }
+                12 => {
+                    let mut il: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::Andi(IL(il), Rd((rf as u8) << 4 | rd));
+                }
+                13 => {
+                    let mut il: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::Ori(IL(il), Rd((rf as u8) << 4 | rd));
+                }
+                14 => {
+                    let mut il: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::Xori(IL(il), Rd((rf as u8) << 4 | rd));
+                }
+                15 => {
+                    let iw = next_word(&mut word_iter, &mut truncated);
+                    inst = Instruction::Subiw(IW(iw), Rd((rf as u8) << 4 | rd));
+                }
+                _ => {
+                    bad = true;
+                }
+            },
+            0b0000110 => match subop {
+                8 => {
+                    let mut il: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    il.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    il.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::Subil(IL(il), Rd((rf as u8) << 4 | rd));
+                }
+                9 => {
+                    let offset = next_word(&mut word_iter, &mut truncated);
+                    inst = Instruction::Callr(Offset(offset), PC(address));
+                }
+                10 => {
+                    let mut address: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::Calla(Address(address));
+                }
+                11 => {
+                    inst = Instruction::Eint;
+                }
+                12 => {
+                    let offset = next_word(&mut word_iter, &mut truncated);
+                    inst = Instruction::Dsj(Rd((rf as u8) << 4 | rd), Offset(offset), PC(address));
+                }
+                13 => {
+                    let offset = next_word(&mut word_iter, &mut truncated);
+                    inst =
+                        Instruction::Dsjeq(Rd((rf as u8) << 4 | rd), Offset(offset), PC(address));
                 }
+                14 => {
+                    let offset = next_word(&mut word_iter, &mut truncated);
+                    inst =
+                        Instruction::Dsjne(Rd((rf as u8) << 4 | rd), Offset(offset), PC(address));
+                }
+                15 => {
+                    inst = Instruction::Setc;
+                }
+                _ => {
+                    bad = true;
+                }
+            },
+            0b0000111 => match subop {
+                8 => {
+                    inst = Instruction::Pixbltll;
+                }
+                9 => {
+                    inst = Instruction::Pixbltlxy;
+                }
+                10 => {
+                    inst = Instruction::Pixbltxyl;
+                }
+                11 => {
+                    inst = Instruction::Pixbltxyxy;
+                }
+                12 => {
+                    inst = Instruction::Pixbltbl;
+                }
+                13 => {
+                    inst = Instruction::Pixbltbxy;
+                }
+                14 => {
+                    inst = Instruction::Filll;
+                }
+                15 => {
+                    inst = Instruction::Fillxy;
+                }
+                _ => {
+                    bad = true;
+                }
+            },
+            0b0001000 | 0b0001001 => {
+                if k == 1 {
+                    inst = Instruction::Inc(Rd((rf as u8) << 4 | rd));
+                } else {
+                    inst = Instruction::Addk(K(k), Rd((rf as u8) << 4 | rd));
+                }
+            }
+            0b0001010 | 0b0001011 => {
+                if k == 1 {
+                    inst = Instruction::Dec(Rd((rf as u8) << 4 | rd));
+                } else {
+                    inst = Instruction::Subk(K(k), Rd((rf as u8) << 4 | rd));
+                }
+            }
+            0b0001100 | 0b0001101 => {
+                inst = Instruction::Movk(K(k), Rd((rf as u8) << 4 | rd));
+            }
+            0b0001110 | 0b0001111 => {
+                // reminder to deal with 1's complement when formatting and assembling
+                inst = Instruction::Btstk(K(k), Rd((rf as u8) << 4 | rd));
+            }
+            0b0010000 | 0b0010001 => {
+                inst = Instruction::Slak(K(k), Rd((rf as u8) << 4 | rd));
+            }
+            0b0010010 | 0b0010011 => {
+                inst = Instruction::Sllk(K(k), Rd((rf as u8) << 4 | rd));
+            }
+            0b0010100 | 0b0010101 => {
+                // reminder to deal with 2's complement when formatting and assembling
+                inst = Instruction::Srak(K(k), Rd((rf as u8) << 4 | rd));
+            }
+            0b0010110 | 0b0010111 => {
+                // reminder to deal with 2's complement when formatting and assembling
+                inst = Instruction::Srlk(K(k), Rd((rf as u8) << 4 | rd));
+            }
+            0b0011000 | 0b0011001 => {
+                inst = Instruction::Rlk(K(k), Rd((rf as u8) << 4 | rd));
+            }
+            // backwards by 0 is the same as forwards by 0, which is the one
+            // the assembler produces
+            0b0011110 | 0b0011111 if k == 0 => {
+                bad = true;
+            }
+            0b0011100..=0b0011111 => {
+                inst = Instruction::Dsjs(D(d), Rd((rf as u8) << 4 | rd), K(k), PC(address));
+            }
+            0b0100000 => {
+                inst = Instruction::Add(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0100001 => {
+                inst = Instruction::Addc(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0100010 => {
+                inst = Instruction::Sub(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0100011 => {
+                inst = Instruction::Subb(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0100100 => {
+                inst = Instruction::Cmp(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0100101 => {
+                inst = Instruction::Btst(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0100110 | 0b0100111 => {
+                inst = Instruction::MoveReg(
+                    Rs((rf as u8) << 4 | rs),
+                    // M set means Rd is in the other file
+                    Rd(((rf ^ f) as u8) << 4 | rd),
+                );
+            }
+            0b0101000 => {
+                inst = Instruction::And(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0101001 => {
+                inst = Instruction::Andn(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0101010 => {
+                inst = Instruction::Or(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0101011 => {
+                if rs == rd {
+                    inst = Instruction::Clr(Rd((rf as u8) << 4 | rd));
+                } else {
+                    inst = Instruction::Xor(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+                }
+            }
+            0b0101100 => {
+                inst = Instruction::Divs(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0101101 => {
+                inst = Instruction::Divu(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0101110 => {
+                inst = Instruction::Mpys(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0101111 => {
+                inst = Instruction::Mpyu(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0110000 => {
+                inst = Instruction::Sla(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0110001 => {
+                inst = Instruction::Sll(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0110010 => {
+                inst = Instruction::Sra(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0110011 => {
+                inst = Instruction::Srl(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0110100 => {
+                inst = Instruction::Rl(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0110101 => {
+                inst = Instruction::Lmo(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0110110 => {
+                inst = Instruction::Mods(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b0110111 => {
+                inst = Instruction::Modu(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b1000000 | 0b1000001 => {
+                inst = Instruction::MoveFieldRegToIndirect(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                );
+            }
+            0b1000010 | 0b1000011 => {
+                inst = Instruction::MoveFieldIndirectToReg(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                );
+            }
+            0b1000100 | 0b1000101 => {
+                inst = Instruction::MoveFieldIndirectToIndirect(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                );
+            }
+            0b1000110 => {
+                inst = Instruction::MovbRegToIndirect(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                );
+            }
+            0b1000111 => {
+                inst = Instruction::MovbIndirectToReg(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                );
+            }
+            0b1001000 | 0b1001001 => {
+                inst = Instruction::MoveFieldRegToIndirectPostinc(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                );
+            }
+            0b1001010 | 0b1001011 => {
+                inst = Instruction::MoveFieldIndirectPostincToReg(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                );
             }
-            None => {
-                break;
+            0b1001100 | 0b1001101 => {
+                inst = Instruction::MoveFieldIndirectToIndirectPostinc(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                );
+            }
+            0b1001110 => {
+                inst = Instruction::MovbIndirectToIndirect(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                );
+            }
+            0b1010000 | 0b1010001 => {
+                inst = Instruction::MoveFieldRegToIndirectPredec(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                );
+            }
+            0b1010010 | 0b1010011 => {
+                inst = Instruction::MoveFieldIndirectPredecToReg(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                );
+            }
+            0b1010100 | 0b1010101 => {
+                inst = Instruction::MoveFieldIndirectToIndirectPredec(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                );
+            }
+            0b1010110 => {
+                let offset = next_word(&mut word_iter, &mut truncated);
+                inst = Instruction::MovbRegToIndirectOffset(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    Offset(offset),
+                );
+            }
+            0b1010111 => {
+                let offset = next_word(&mut word_iter, &mut truncated);
+                inst = Instruction::MovbIndirectOffsetToReg(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    Offset(offset),
+                );
+            }
+            0b1011000 | 0b1011001 => {
+                let offset = next_word(&mut word_iter, &mut truncated);
+                inst = Instruction::MoveFieldRegToIndirectOffset(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                    Offset(offset),
+                );
+            }
+            0b1011010 | 0b1011011 => {
+                let offset = next_word(&mut word_iter, &mut truncated);
+                inst = Instruction::MoveFieldIndirectOffsetToReg(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                    Offset(offset),
+                );
+            }
+            0b1011100 | 0b1011101 => {
+                let src_offset = next_word(&mut word_iter, &mut truncated);
+                let dst_offset = next_word(&mut word_iter, &mut truncated);
+                inst = Instruction::MoveFieldIndirectOffsetToIndirectOffset(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                    Offset(src_offset),
+                    Offset(dst_offset),
+                );
+            }
+            0b1011110 => {
+                let src_offset = next_word(&mut word_iter, &mut truncated);
+                let dst_offset = next_word(&mut word_iter, &mut truncated);
+                inst = Instruction::MovbIndirectOffsetToIndirectOffset(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    Offset(src_offset),
+                    Offset(dst_offset),
+                );
+            }
+            0b1100000..=0b1100111 => {
+                let lower8 = word.get(0..=7).unwrap().load::<u8>();
+                if lower8 == 0x80 {
+                    let mut address: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::Ja(Condition(cc), Address(address));
+                } else if lower8 == 0x00 {
+                    let offset = next_word(&mut word_iter, &mut truncated);
+                    inst = Instruction::Jr(Condition(cc), Offset(offset), PC(address));
+                } else {
+                    inst = Instruction::Jrs(Condition(cc), Offset8(lower8), PC(address));
+                }
+            }
+            0b1101000 | 0b1101001 => {
+                let offset = next_word(&mut word_iter, &mut truncated);
+                inst = Instruction::MoveFieldIndirectOffsetToIndirectPostinc(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                    F(f),
+                    Offset(offset),
+                );
+            }
+            0b1101010 | 0b1101011 => match subop {
+                0 => {
+                    let mut address: u32 = 0;
+                    let lsb = next_word(&mut word_iter, &mut truncated);
+                    let msb = next_word(&mut word_iter, &mut truncated);
+                    address.view_bits_mut::<Lsb0>()[0..=15].store::<u16>(lsb);
+                    address.view_bits_mut::<Lsb0>()[16..=31].store::<u16>(msb);
+                    inst = Instruction::MoveFieldAbsoluteToIndirectPostinc(
+                        Address(address),
+                        Rd((rf as u8) << 4 | rd),
+                        F(f),
+                    );
+                }
+                8 => {
+                    inst = Instruction::Exgf(Rd((rf as u8) << 4 | rd), F(f));
+                }
+                _ => {
+                    bad = true;
+                }
+            },
+            0b1101111 => {
+                inst = Instruction::Line(Z(z));
+            }
+            0b1110000 => {
+                inst = Instruction::Addxy(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b1110001 => {
+                inst = Instruction::Subxy(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b1110010 => {
+                inst = Instruction::Cmpxy(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b1110011 => {
+                inst = Instruction::Cpw(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b1110100 => {
+                inst = Instruction::Cvxyl(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b1110110 => {
+                inst = Instruction::Movx(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b1110111 => {
+                inst = Instruction::Movy(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b1111000 => {
+                inst = Instruction::PixtRegToIndirectxy(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                );
+            }
+            0b1111001 => {
+                inst = Instruction::PixtIndirectxyToReg(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                );
+            }
+            0b1111010 => {
+                inst = Instruction::PixtIndirectxyToIndirectxy(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                );
+            }
+            0b1111011 => {
+                inst = Instruction::Drav(Rs((rf as u8) << 4 | rs), Rd((rf as u8) << 4 | rd));
+            }
+            0b1111100 => {
+                inst = Instruction::PixtRegToIndirect(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                );
+            }
+            0b1111101 => {
+                inst = Instruction::PixtIndirectToReg(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                );
+            }
+            0b1111110 => {
+                inst = Instruction::PixtIndirectToIndirect(
+                    Rs((rf as u8) << 4 | rs),
+                    Rd((rf as u8) << 4 | rd),
+                );
+            }
+            _ => {
+                bad = true;
             }
         }
+        if truncated {
+            return Err(Error::Truncated { address });
+        }
+        if bad {
+            return Err(Error::InvalidEncoding {
+                address,
+                word: first,
+            });
+        }
+        Ok((inst, words.len() - word_iter.len()))
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = DecodedInstruction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.words[self.pos..];
+        let first = *rest.first()?;
+        let address = self.start.offset_words(self.pos as i64);
+        let (inst, len) = match Self::decode(rest, address) {
+            Ok(decoded) if !self.truncated => decoded,
+            Err(Error::Truncated { .. }) => {
+                self.truncated = true;
+                (Instruction::Dw(IW(first)), 1)
+            }
+            _ => (Instruction::Dw(IW(first)), 1),
+        };
+        self.pos += len;
+        Some(DecodedInstruction::new(address, inst, &rest[..len]))
+    }
+}
+
+// the next extension word, or 0 with truncated set if the buffer has run out
+fn next_word(word_iter: &mut core::slice::Iter<u16>, truncated: &mut bool) -> u16 {
+    match word_iter.next() {
+        Some(word) => *word,
+        None => {
+            *truncated = true;
+            0
+        }
+    }
+}
+
+fn sign_extend(word: u16) -> IL {
+    IL(word as i16 as u32)
+}
+
+// what the parser needs to pick the long form of an immediate
+fn long_suffix(value: u32) -> &'static str {
+    if (i16::MIN as i32..=i16::MAX as i32).contains(&(value as i32)) {
+        ", L"
+    } else {
+        ""
     }
-    inst_vec
+}
+
+// ", A0, A1, SP" for a mask with bit n set for register n, in the file of
+// the pointer register
+fn reglist_str(pointer: u8, list: u16) -> String {
+    let reg_letter = if pointer & 0x10 != 0 { 'B' } else { 'A' };
+    let mut reglist_str = String::new();
+    for reg_number in list.view_bits::<Lsb0>().iter_ones() {
+        if reg_number == 15 {
+            write!(reglist_str, ", SP").unwrap();
+        } else {
+            write!(reglist_str, ", {}{}", reg_letter, reg_number).unwrap();
+        }
+    }
+    reglist_str
 }
 
 impl fmt::Display for Instruction {
@@ -1357,16 +1052,43 @@ impl fmt::Display for Instruction {
             Instruction::Jump(rs) | Instruction::Call(rs) | Instruction::Putst(rs) => {
                 write!(fmt, "{} {}", self.get_mnemonic(), rs)
             }
-            Instruction::Moviw(iw, rd)
-            | Instruction::Subiw(iw, rd)
-            | Instruction::Addiw(iw, rd) => {
-                write!(fmt, "{} {}, {}", self.get_mnemonic(), iw, rd)
+            // the short forms sign extend their word, print the value the
+            // register actually sees
+            Instruction::Moviw(iw, rd) | Instruction::Addiw(iw, rd) => {
+                write!(fmt, "{} {}, {}", self.get_mnemonic(), sign_extend(iw.0), rd)
             }
-            Instruction::Addil(il, rd)
-            | Instruction::Subil(il, rd)
-            | Instruction::Movil(il, rd)
-            | Instruction::Xori(il, rd)
-            | Instruction::Ori(il, rd) => {
+            Instruction::Subiw(iw, rd) | Instruction::Cmpiw(iw, rd) => {
+                write!(
+                    fmt,
+                    "{} {}, {}",
+                    self.get_mnemonic(),
+                    sign_extend(!iw.0),
+                    rd
+                )
+            }
+            // a long immediate that would fit a word needs the L, or it would
+            // reassemble to the short form
+            Instruction::Addil(il, rd) | Instruction::Movil(il, rd) => {
+                write!(
+                    fmt,
+                    "{} {}, {}{}",
+                    self.get_mnemonic(),
+                    il,
+                    rd,
+                    long_suffix(il.0)
+                )
+            }
+            Instruction::Subil(il, rd) | Instruction::Cmpil(il, rd) => {
+                write!(
+                    fmt,
+                    "{} {}, {}{}",
+                    self.get_mnemonic(),
+                    IL(!il.0),
+                    rd,
+                    long_suffix(!il.0)
+                )
+            }
+            Instruction::Xori(il, rd) | Instruction::Ori(il, rd) => {
                 write!(fmt, "{} {}, {}", self.get_mnemonic(), il, rd)
             }
             Instruction::Setf(fs, fe, f) => {
@@ -1384,40 +1106,29 @@ impl fmt::Display for Instruction {
             Instruction::Callr(offset, pc) => {
                 write!(
                     fmt,
-                    "{} {:X}h",
+                    "{} {}",
                     self.get_mnemonic(),
-                    ((pc.0 as i64 + offset.0 as i16 as i64) * 16) as u32 + 32
+                    pc.0.offset_words(offset.0 as i16 as i64 + 2)
                 )
             }
             Instruction::Jrs(condition, off8, pc) => {
                 write!(
                     fmt,
-                    "{}{} {:X}h",
+                    "{}{} {}",
                     self.get_mnemonic(),
                     condition,
-                    (((pc.0 as i64 + off8.0 as i8 as i64) * 16) + 16) as u32
+                    pc.0.offset_words(off8.0 as i8 as i64 + 1)
                 )
             }
             Instruction::Ja(condition, address) => {
-                write!(
-                    fmt,
-                    "{}{}, {:X}h",
-                    self.get_mnemonic(),
-                    condition,
-                    address.0 as u32
-                )
+                write!(fmt, "{}{} {:X}h", self.get_mnemonic(), condition, address.0)
             }
             Instruction::Rets(n) => {
-                write!(
-                    fmt,
-                    "{} {}",
-                    self.get_mnemonic(),
-                    if n.0 > 0 {
-                        n.to_string()
-                    } else {
-                        String::new()
-                    }
-                )
+                if n.0 > 0 {
+                    write!(fmt, "{} {}", self.get_mnemonic(), n)
+                } else {
+                    write!(fmt, "{}", self.get_mnemonic())
+                }
             }
             Instruction::PixtRegToIndirect(rs, rd) => {
                 write!(fmt, "{} {}, *{}", self.get_mnemonic(), rs, rd)
@@ -1435,30 +1146,27 @@ impl fmt::Display for Instruction {
                 write!(fmt, "{} *{}, XY, {}", self.get_mnemonic(), rs, rd)
             }
             Instruction::PixtIndirectxyToIndirectxy(rs, rd) => {
-                write!(fmt, "{} *{} ,XY, *{}, XY", self.get_mnemonic(), rs, rd)
+                write!(fmt, "{} *{}, XY, *{}, XY", self.get_mnemonic(), rs, rd)
             }
             Instruction::Dsjs(d, rd, k, pc) => {
                 if d.0 {
                     write!(
                         fmt,
-                        "{} {}, {:X}h",
+                        "{} {}, {}",
                         self.get_mnemonic(),
                         rd,
-                        (pc.0 - k.0 as u32) * 16 + 16
+                        pc.0.offset_words(1 - k.0 as i64)
                     )
                 } else {
                     write!(
                         fmt,
-                        "{} {}, {:X}h",
+                        "{} {}, {}",
                         self.get_mnemonic(),
                         rd,
-                        (pc.0 + k.0 as u32) * 16 + 16
+                        pc.0.offset_words(1 + k.0 as i64)
                     )
                 }
             }
-            Instruction::Cmpil(il, rd) => {
-                write!(fmt, "{} {:08X}h, {}", self.get_mnemonic(), !il.0, rd)
-            }
             Instruction::MovbRegToIndirect(rs, rd) => {
                 write!(fmt, "{} {}, *{}", self.get_mnemonic(), rs, rd)
             }
@@ -1484,8 +1192,8 @@ impl fmt::Display for Instruction {
                     "{} *{}({}), {}",
                     self.get_mnemonic(),
                     rs,
-                    rd,
-                    offset.0 as i16
+                    offset.0 as i16,
+                    rd
                 )
             }
             Instruction::MovbIndirectOffsetToIndirectOffset(rs, rd, offset, offset2) => {
@@ -1494,8 +1202,8 @@ impl fmt::Display for Instruction {
                     "{} *{}({}), *{}({})",
                     self.get_mnemonic(),
                     rs,
-                    rd,
                     offset.0 as i16,
+                    rd,
                     offset2.0 as i16
                 )
             }
@@ -1528,7 +1236,7 @@ impl fmt::Display for Instruction {
                 write!(fmt, "{} @{}, *{}+, {}", self.get_mnemonic(), addr, rd, f)
             }
             Instruction::MoveFieldRegToIndirect(rs, rd, f) => {
-                write!(fmt, "{} *{}, {}, {}", self.get_mnemonic(), rs, rd, f)
+                write!(fmt, "{} {}, *{}, {}", self.get_mnemonic(), rs, rd, f)
             }
             Instruction::MoveFieldRegToIndirectOffset(rs, rd, f, offset) => {
                 write!(
@@ -1537,7 +1245,7 @@ impl fmt::Display for Instruction {
                     self.get_mnemonic(),
                     rs,
                     rd,
-                    offset.0 as u16,
+                    offset.0,
                     f
                 )
             }
@@ -1571,7 +1279,7 @@ impl fmt::Display for Instruction {
                     "{} *{}({:X}h), {}, {}",
                     self.get_mnemonic(),
                     rs,
-                    offset.0 as u16,
+                    offset.0,
                     rd,
                     f
                 )
@@ -1582,7 +1290,7 @@ impl fmt::Display for Instruction {
                     "{} *{}({:X}h), *{}+, {}",
                     self.get_mnemonic(),
                     rs,
-                    offset.0 as u16,
+                    offset.0,
                     rd,
                     f
                 )
@@ -1593,32 +1301,30 @@ impl fmt::Display for Instruction {
                     "{} *{}({:X}h), *{}({:X}h), {}",
                     self.get_mnemonic(),
                     rs,
-                    offset1.0 as u16,
+                    offset1.0,
                     rd,
-                    offset2.0 as u16,
+                    offset2.0,
                     f
                 )
             }
             Instruction::Andi(il, rd) => {
                 let ones_comp = IL(!il.0);
-                write!(fmt, "{}, {}, {}", self.get_mnemonic(), ones_comp, rd)
+                write!(fmt, "{} {}, {}", self.get_mnemonic(), ones_comp, rd)
             }
-            Instruction::Cmpiw(iw, rd) => {
-                write!(fmt, "{} {:08X}h, {}", self.get_mnemonic(), !iw.0, rd)
-            }
-            Instruction::Sllk(k, rd)
-            | Instruction::Rlk(k, rd)
-            | Instruction::Srlk(k, rd)
-            | Instruction::Srak(k, rd)
-            | Instruction::Slak(k, rd) => {
+            Instruction::Sllk(k, rd) | Instruction::Rlk(k, rd) | Instruction::Slak(k, rd) => {
                 write!(fmt, "{} {}, {}", self.get_mnemonic(), k, rd)
             }
+            // right shifts hold the two's complement of the shift amount
+            Instruction::Srlk(k, rd) | Instruction::Srak(k, rd) => {
+                let amount = K(0u8.wrapping_sub(k.0) & 0x1f);
+                write!(fmt, "{} {}, {}", self.get_mnemonic(), amount, rd)
+            }
             Instruction::Addk(k, rd) | Instruction::Subk(k, rd) | Instruction::Movk(k, rd) => {
                 let special_k = if k.0 == 0 { K(32) } else { *k };
                 write!(fmt, "{} {}, {}", self.get_mnemonic(), special_k, rd)
             }
-            Instruction::Exgpc(rd, f) => {
-                write!(fmt, "{} {}, {}", self.get_mnemonic(), rd, f)
+            Instruction::Exgpc(rd, _) => {
+                write!(fmt, "{} {}", self.get_mnemonic(), rd)
             }
             Instruction::Line(z) => {
                 write!(fmt, "{} {}", self.get_mnemonic(), z)
@@ -1627,99 +1333,456 @@ impl fmt::Display for Instruction {
                 write!(fmt, "{} {:04X}h", self.get_mnemonic(), word.0)
             }
             Instruction::Jr(condition, offset, pc) => {
+                // a target the short form could reach has to be marked long,
+                // 0 is the short form's long marker
+                let short = offset.0 as i16 as i64 + 1;
                 write!(
                     fmt,
-                    "{}{} {:X}h",
+                    "{}{} {}{}",
                     self.get_mnemonic(),
                     condition,
-                    (((pc.0 as i64 + offset.0 as i16 as i64) * 16) + 16) as u32
-                )
-            }
-
-            Instruction::Mmtm(rd, reglist) => {
-                let rf = rd.0.view_bits::<Lsb0>()[4];
-                let mut reg_letter = 'A';
-                if rf {
-                    reg_letter = 'B';
-                }
-                let mut reglist_str = String::new();
-                let iter = reglist.0.view_bits::<Lsb0>().iter_ones().peekable();
-                for reg_number in iter {
-                    if reg_number == 15 {
-                        write!(reglist_str, "SP").unwrap();
+                    pc.0.offset_words(offset.0 as i16 as i64 + 2),
+                    if short != 0 && (-127..=127).contains(&short) {
+                        ", L"
                     } else {
-                        write!(reglist_str, "{}{}", reg_letter, reg_number).unwrap();
+                        ""
                     }
-                    write!(reglist_str, ", ").unwrap();
-                }
-                // can't be arsed to come up with logic to avoid writing an extraneous comma
-                // fix it in post!
-                reglist_str = reglist_str[0..reglist_str.len() - 2].to_string();
-                write!(fmt, "{} {}, {}", self.get_mnemonic(), rd, reglist_str)
+                )
             }
 
-            // The type system has betrayed me!
-            // Rd and Rs are different types so MMTM and MMFM can't be in the same match block
-            // YOLO copy-pasta
-            Instruction::Mmfm(rs, reglist) => {
-                let rf = rs.0.view_bits::<Lsb0>()[4];
-                let mut reg_letter = 'A';
-                if rf {
-                    reg_letter = 'B';
-                }
-                let mut reglist_str = String::new();
-                let iter = reglist.0.view_bits::<Lsb0>().iter_ones().peekable();
-                for reg_number in iter {
-                    if reg_number == 15 {
-                        write!(reglist_str, "SP").unwrap();
-                    } else {
-                        write!(reglist_str, "{}{}", reg_letter, reg_number).unwrap();
-                    }
-                    write!(reglist_str, ", ").unwrap();
-                }
-                // can't be arsed to come up with logic to avoid writing an extraneous comma
-                // fix it in post!
-                reglist_str = reglist_str[0..reglist_str.len() - 2].to_string();
+            // MMTM lists R0 in the top bit of the mask, MMFM in the bottom one
+            Instruction::Mmtm(rd, reglist) => write!(
+                fmt,
+                "{} {}{}",
+                self.get_mnemonic(),
+                rd,
+                reglist_str(rd.0, reglist.0.reverse_bits())
+            ),
+            Instruction::Mmfm(rs, reglist) => write!(
+                fmt,
+                "{} {}{}",
+                self.get_mnemonic(),
+                rs,
+                reglist_str(rs.0, reglist.0)
+            ),
 
-                write!(fmt, "{} {}, {}", self.get_mnemonic(), rs, reglist_str)
+            // stored as one's complement
+            Instruction::Btstk(k, rd) => {
+                write!(fmt, "{} {}, {}", self.get_mnemonic(), K(!k.0 & 0x1f), rd)
             }
-
-            Instruction::Btstk(_, _)
-            | Instruction::Dsj(_, _)
-            | Instruction::Dsjne(_, _)
-            | Instruction::Dsjeq(_, _) => {
-                write!(fmt, "UNIMPL {:?}", self)
+            Instruction::Dsj(rd, offset, pc)
+            | Instruction::Dsjeq(rd, offset, pc)
+            | Instruction::Dsjne(rd, offset, pc) => {
+                write!(
+                    fmt,
+                    "{} {}, {}",
+                    self.get_mnemonic(),
+                    rd,
+                    pc.0.offset_words(offset.0 as i16 as i64 + 2)
+                )
             }
         }
     }
 }
 
-pub fn disassemble_stage2(stage1_output: Vec<(usize, Instruction, Vec<u16>)>) -> String {
-    let mut disassembly = String::new();
+pub fn disassemble_stage2(stage1_output: Vec<DecodedInstruction>) -> String {
+    disassemble_stage2_with(stage1_output, &Listing::default())
+}
+
+// Same as disassemble_stage2() but with a line for each label that's on an
+// instruction and labels in place of the addresses they're for. Labels
+// anywhere else are defined with .set at the top if an operand uses them.
+pub fn disassemble_stage2_labeled(
+    stage1_output: Vec<DecodedInstruction>,
+    labels: &BTreeMap<BitAddress, String>,
+) -> String {
+    let listing = Listing {
+        labels: labels.clone(),
+        ..Listing::default()
+    };
+    disassemble_stage2_with(stage1_output, &listing)
+}
+
+// What goes into the listing besides the instructions
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+    // see disassemble_stage2_labeled()
+    pub labels: BTreeMap<BitAddress, String>,
+    // comment graphics instructions with the B registers they use without
+    // naming them, e.g. "; reads DADDR DPTCH DYDX COLOR1"
+    pub implicit_registers: bool,
+    // the field settings going into each instruction, from
+    // fields::track_fields(), for commenting the ones that use a field
+    pub fields: BTreeMap<BitAddress, FieldState>,
+    // from vectors::read_vectors(), for naming the handler each TRAP goes to
+    pub vectors: Vec<Vector>,
+    // from symbol_file::symbol_kinds(), what's at the word, long and data
+    // ones is listed as .word, .long or DW instead of being decoded
+    pub kinds: BTreeMap<BitAddress, Option<SymbolKind>>,
+}
+
+pub fn disassemble_stage2_with(
+    stage1_output: Vec<DecodedInstruction>,
+    listing: &Listing,
+) -> String {
+    let mut disassembly = vec![];
+    // writing to a Vec can't fail
+    try_disassemble_stage2_with(stage1_output, listing, &mut disassembly).unwrap();
+    String::from_utf8(disassembly).unwrap()
+}
 
-    for (pc, inst, words) in stage1_output {
-        let mut hexdump = String::new();
-        write!(hexdump, "{:04X?}", words).unwrap();
-        hexdump = hexdump.replace("[", "");
-        hexdump = hexdump.replace("]", "");
-        hexdump = hexdump.replace(",", "");
+// Same as disassemble_stage2() but writes each line out as it goes
+pub fn try_disassemble_stage2(
+    stage1_output: Vec<DecodedInstruction>,
+    out: &mut impl io::Write,
+) -> Result<(), Error> {
+    try_disassemble_stage2_with(stage1_output, &Listing::default(), out)
+}
 
+pub fn try_disassemble_stage2_labeled(
+    stage1_output: Vec<DecodedInstruction>,
+    labels: &BTreeMap<BitAddress, String>,
+    out: &mut impl io::Write,
+) -> Result<(), Error> {
+    let listing = Listing {
+        labels: labels.clone(),
+        ..Listing::default()
+    };
+    try_disassemble_stage2_with(stage1_output, &listing, out)
+}
+
+pub fn try_disassemble_stage2_with(
+    stage1_output: Vec<DecodedInstruction>,
+    listing: &Listing,
+    out: &mut impl io::Write,
+) -> Result<(), Error> {
+    let (stage1_output, inside) = list_data(stage1_output, listing);
+    let trimmed;
+    let listing = if inside.is_empty() {
+        listing
+    } else {
+        trimmed = Listing {
+            labels: listing
+                .labels
+                .iter()
+                .filter(|(address, _)| !inside.contains(address))
+                .map(|(address, label)| (*address, label.clone()))
+                .collect(),
+            ..listing.clone()
+        };
+        &trimmed
+    };
+    let labels = &listing.labels;
+    let first = match stage1_output.first() {
+        Some(decoded) => decoded.address,
+        None => return Ok(()),
+    };
+    let starts: BTreeSet<_> = stage1_output
+        .iter()
+        .map(|decoded| decoded.address)
+        .collect();
+    let equates: BTreeSet<_> = stage1_output
+        .iter()
+        .flat_map(|decoded| address_operands(&decoded.instruction))
+        .map(|(_, address)| address)
+        .filter(|address| labels.contains_key(address) && !starts.contains(address))
+        .collect();
+    for address in equates {
         writeln!(
-            disassembly,
-            "{:08X}:\t{}{}{}",
-            pc * 16,
-            inst,
-            " ".repeat(60 - inst.to_string().len()),
-            hexdump
+            out,
+            "{:08X}:\t{} .set {}",
+            first.0, labels[&address], address
+        )?;
+    }
+    for decoded in stage1_output {
+        out.write_all(listing_line(&decoded, listing).as_bytes())?;
+    }
+    Ok(())
+}
+
+// Redoes the word, long and data symbols' addresses as data, cutting up
+// anything decoded over them. A word or long is one entry, plain data is a DW
+// per word up to the next symbol. Whatever is left of a cut instruction
+// becomes DWs too. Also returns the addresses inside the data, where any
+// generated labels would only be naming a misreading.
+fn list_data(
+    stage1_output: Vec<DecodedInstruction>,
+    listing: &Listing,
+) -> (Vec<DecodedInstruction>, BTreeSet<BitAddress>) {
+    let is_data = |kind: Option<&Option<SymbolKind>>| {
+        matches!(
+            kind,
+            Some(Some(SymbolKind::Word | SymbolKind::Long | SymbolKind::Data))
         )
-        .unwrap();
+    };
+    let first = match stage1_output.first() {
+        Some(decoded) if listing.kinds.values().any(|kind| is_data(Some(kind))) => decoded.address,
+        _ => return (stage1_output, BTreeSet::new()),
+    };
+    // by word position rather than address, a ROM with the vectors in it
+    // ends right at the top of memory
+    let mut words = vec![];
+    let mut starts = BTreeMap::new();
+    let mut odd_byte = None;
+    for decoded in stage1_output {
+        // the odd byte at the end can't be part of anything
+        if decoded.size % 16 != 0 {
+            odd_byte = Some(decoded);
+        } else {
+            starts.insert(words.len(), decoded);
+            words.extend_from_slice(decoded.words());
+        }
+    }
+    let address = |pos: usize| first.offset_words(pos as i64);
+    let kind = |pos: usize| listing.kinds.get(&address(pos));
+    let dw = |pos: usize| {
+        DecodedInstruction::new(address(pos), Instruction::Dw(IW(words[pos])), &[words[pos]])
+    };
+
+    let mut output = vec![];
+    let mut inside = BTreeSet::new();
+    let mut pos = 0;
+    while pos < words.len() {
+        let len = match (kind(pos), starts.get(&pos)) {
+            (Some(Some(SymbolKind::Long)), _) if pos + 1 < words.len() => {
+                output.push(DecodedInstruction::new(
+                    address(pos),
+                    Instruction::Dw(IW(words[pos])),
+                    &words[pos..pos + 2],
+                ));
+                2
+            }
+            (Some(Some(SymbolKind::Word | SymbolKind::Long)), _) => {
+                output.push(dw(pos));
+                1
+            }
+            (Some(Some(SymbolKind::Data)), _) => {
+                let end = (pos + 1..words.len())
+                    .find(|&end| kind(end).is_some())
+                    .unwrap_or(words.len());
+                output.extend((pos..end).map(dw));
+                end - pos
+            }
+            // an instruction with no data under it
+            (_, Some(decoded))
+                if !(pos + 1..pos + decoded.words().len()).any(|pos| is_data(kind(pos))) =>
+            {
+                output.push(*decoded);
+                pos += decoded.words().len();
+                continue;
+            }
+            _ => {
+                output.push(dw(pos));
+                pos += 1;
+                continue;
+            }
+        };
+        inside.extend(
+            (pos + 1..pos + len)
+                .filter(|&pos| kind(pos).is_none())
+                .map(address),
+        );
+        pos += len;
+    }
+    output.extend(odd_byte);
+    (output, inside)
+}
+
+// A label for everything branched to, SUB_ for anything called (even if it's
+// also jumped to) and L_ for the rest. Targets in the middle of an instruction
+// are left alone, they'd only be labeling a misreading.
+pub fn branch_labels(stage1_output: &[DecodedInstruction]) -> BTreeMap<BitAddress, String> {
+    let starts: BTreeSet<_> = stage1_output
+        .iter()
+        .map(|decoded| decoded.address)
+        .collect();
+    let mut labels = BTreeMap::new();
+    for decoded in stage1_output {
+        let target = match decoded.branch_target() {
+            Some(target) if starts.contains(&target) => target,
+            _ => continue,
+        };
+        if decoded.is_call() {
+            labels.insert(target, format!("SUB_{:08X}", target.0));
+        } else {
+            labels
+                .entry(target)
+                .or_insert_with(|| format!("L_{:08X}", target.0));
+        }
+    }
+    labels
+}
+
+// What the disassembler labels by default: the vector table's handlers, then
+// every other branch target, then the I/O registers
+pub fn auto_labels(
+    words: &[u16],
+    start: BitAddress,
+    stage1_output: &[DecodedInstruction],
+) -> BTreeMap<BitAddress, String> {
+    let mut labels = vector_labels(&read_vectors(words, start));
+    for (address, label) in branch_labels(stage1_output)
+        .into_iter()
+        .chain(io_register_labels())
+    {
+        labels.entry(address).or_insert(label);
+    }
+    labels
+}
+
+// Operands that are addresses, with the character in front of them: branch
+// and call targets, @address operands and MOVI immediates (which are usually
+// pointers when they match a label)
+fn address_operands(instruction: &Instruction) -> Vec<(char, BitAddress)> {
+    let mut operands: Vec<_> = instruction
+        .absolute_addresses()
+        .into_iter()
+        .map(|address| ('@', address))
+        .collect();
+    operands.extend(instruction.branch_target().map(|target| (' ', target)));
+    match instruction {
+        Instruction::Moviw(iw, _) => operands.push((' ', BitAddress(sign_extend(iw.0).0))),
+        Instruction::Movil(il, _) => operands.push((' ', BitAddress(il.0))),
+        _ => {}
+    }
+    operands
+}
+
+fn listing_line(decoded: &DecodedInstruction, listing: &Listing) -> String {
+    let labels = &listing.labels;
+    let mut line = String::new();
+    if let Some(label) = labels.get(&decoded.address) {
+        writeln!(line, "{:08X}:\t{}:", decoded.address.0, label).unwrap();
+    }
+
+    let mut hexdump = String::new();
+    write!(hexdump, "{:04X?}", decoded.words()).unwrap();
+    hexdump = hexdump.replace("[", "");
+    hexdump = hexdump.replace("]", "");
+    hexdump = hexdump.replace(",", "");
+
+    let (mut text, labeled) = labeled_text(&decoded.instruction, labels);
+    // The assembler sizes JR and MOVI by where it guesses labels it hasn't
+    // placed yet are, and growing one later moves everything after it, so
+    // pin the size down whenever a label went in
+    let suffix = match decoded.instruction {
+        Instruction::Jr(..) | Instruction::Movil(..) => ", L",
+        Instruction::Moviw(..) => ", W",
+        _ => "",
+    };
+    if labeled && !text.ends_with(suffix) {
+        text.push_str(suffix);
+    }
+    // the odd byte at the end, which as DW would reassemble to a whole word,
+    // and the --symbols words and longs from list_data()
+    match (decoded.instruction, decoded.words()) {
+        (Instruction::Dw(_), [byte]) if decoded.size == 8 => text = format!(".byte {:02X}h", byte),
+        (Instruction::Dw(_), [low, high]) => text = format!(".long {:04X}{:04X}h", high, low),
+        (Instruction::Dw(_), [word])
+            if listing.kinds.get(&decoded.address) == Some(&Some(SymbolKind::Word)) =>
+        {
+            text = format!(".word {:04X}h", word)
+        }
+        _ => {}
+    }
+    let mut comments = vec![];
+    // the instruction itself wouldn't reassemble to the same words
+    let dont_care = decoded.dont_care_bits();
+    if dont_care != 0 {
+        comments.push(format!("{} with don't-care bits {:04X}h", text, dont_care));
+        let words: Vec<_> = decoded
+            .words()
+            .iter()
+            .map(|word| format!("{:04X}h", word))
+            .collect();
+        text = format!(".word {}", words.join(", "));
+    }
+    if let Instruction::Trap(n) = decoded.instruction {
+        let handler = listing
+            .vectors
+            .iter()
+            .find(|vector| vector.trap == n.0)
+            .and_then(|vector| labels.get(&vector.handler));
+        match (hardware_trap_name(n.0), handler) {
+            (Some(name), Some(handler)) if name != handler => {
+                comments.push(format!("{} -> {}", name, handler))
+            }
+            (_, Some(handler)) => comments.push(format!("-> {}", handler)),
+            (Some(name), None) => comments.push(name.to_string()),
+            (None, None) => {}
+        }
+    }
+    if let Some(state) = listing.fields.get(&decoded.address) {
+        comments.extend(state.describe(&decoded.instruction));
+    }
+    if listing.implicit_registers {
+        let names = |regs: BTreeSet<Reg>| {
+            let names: Vec<_> = regs.into_iter().filter_map(Reg::graphics_name).collect();
+            names.join(" ")
+        };
+        let reads = names(decoded.instruction.implicit_reads());
+        let writes = names(decoded.instruction.implicit_writes());
+        if !reads.is_empty() {
+            comments.push(format!("reads {}", reads));
+        }
+        if !writes.is_empty() {
+            comments.push(format!("writes {}", writes));
+        }
+    }
+    write!(
+        line,
+        "{:08X}:\t{}{}{}",
+        decoded.address.0,
+        text,
+        " ".repeat(60usize.saturating_sub(text.len()).max(1)),
+        hexdump
+    )
+    .unwrap();
+    // after the hexdump, lined up for the longest instruction
+    if !comments.is_empty() {
+        let pad = 24usize.saturating_sub(hexdump.len());
+        write!(line, "{} ; {}", " ".repeat(pad), comments.join(", ")).unwrap();
+    }
+    line.push('\n');
+    line
+}
+
+// The instruction with any of its addresses that have labels replaced by
+// them, and whether there were any
+pub(crate) fn labeled_text(
+    instruction: &Instruction,
+    labels: &BTreeMap<BitAddress, String>,
+) -> (String, bool) {
+    let mut text = instruction.to_string();
+    let mut labeled = false;
+    for (prefix, address) in address_operands(instruction) {
+        if let Some(label) = labels.get(&address) {
+            text = replace_operand(&text, prefix, &address.to_string(), label);
+            labeled = true;
+        }
+    }
+    (text, labeled)
+}
+
+// the operand has to be all of `old`, a target of 1000h mustn't match 11000h
+fn replace_operand(text: &str, prefix: char, old: &str, new: &str) -> String {
+    for (i, _) in text.match_indices(old) {
+        let before = text[..i].chars().next_back();
+        let after = text[i + old.len()..].chars().next();
+        if before == Some(prefix) && matches!(after, None | Some(',')) {
+            return format!("{}{}{}", &text[..i], new, &text[i + old.len()..]);
+        }
     }
-    disassembly
+    text.to_string()
 }
 
-pub fn disassemble(bytebuf: &[u8], start_addr: usize) {
-    println!(
-        "{}",
-        disassemble_stage2(disassemble_stage1(bytebuf, start_addr))
-    );
+pub fn disassemble(bytebuf: &[u8], start: BitAddress) -> Result<(), Error> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let (words, _) = to_words(bytebuf);
+    let inst_vec = disassemble_stage1(bytebuf, start);
+    let labels = auto_labels(&words, start, &inst_vec);
+    try_disassemble_stage2_labeled(inst_vec, &labels, &mut out)?;
+    io::Write::flush(&mut out)?;
+    Ok(())
 }
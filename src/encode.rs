@@ -0,0 +1,300 @@
+use core::ops::Deref;
+
+use crate::instruction::Instruction;
+use crate::symbol::{Rd, Rs, F};
+
+// The inverse of disassemble_stage1: every Instruction holds its operands the
+// way they are stored in the opcode (one's complemented immediates, K = 0 for
+// 32 and so on), so this is mostly shifting fields back into place.
+
+fn reg(r: u8) -> u16 {
+    (r & 0x1f) as u16
+}
+
+// rs only contributes its register number, the file bit comes from rd
+fn regs(rs: Rs, rd: Rd) -> u16 {
+    ((rs.0 & 0xf) as u16) << 5 | reg(rd.0)
+}
+
+fn field(f: F) -> u16 {
+    (f.0 as u16) << 9
+}
+
+fn k(k: u8) -> u16 {
+    ((k & 0x1f) as u16) << 5
+}
+
+fn long(value: u32) -> [u16; 2] {
+    [value as u16, (value >> 16) as u16]
+}
+
+// An instruction's words, kept on the stack since there are at most 5 of
+// them and the decoder checks every instruction it finds against this
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoding {
+    words: [u16; 5],
+    len: usize,
+}
+
+impl Encoding {
+    fn new(words: &[u16]) -> Self {
+        let mut encoding = Encoding {
+            words: [0; 5],
+            len: words.len(),
+        };
+        encoding.words[..words.len()].copy_from_slice(words);
+        encoding
+    }
+}
+
+impl Deref for Encoding {
+    type Target = [u16];
+
+    fn deref(&self) -> &[u16] {
+        &self.words[..self.len]
+    }
+}
+
+impl Instruction {
+    pub fn encode(&self) -> Vec<u16> {
+        self.encoding().to_vec()
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        match *self {
+            Self::Rev(rd) => Encoding::new(&[0x0020 | reg(rd.0)]),
+            Self::Emu => Encoding::new(&[0x0100]),
+            Self::Exgpc(rd, _) => Encoding::new(&[0x0120 | reg(rd.0)]),
+            Self::Getpc(rd) => Encoding::new(&[0x0140 | reg(rd.0)]),
+            Self::Jump(rs) => Encoding::new(&[0x0160 | reg(rs.0)]),
+            Self::Getst(rd) => Encoding::new(&[0x0180 | reg(rd.0)]),
+            Self::Putst(rs) => Encoding::new(&[0x01a0 | reg(rs.0)]),
+            Self::Popst => Encoding::new(&[0x01c0]),
+            Self::Pushst => Encoding::new(&[0x01e0]),
+            Self::Nop => Encoding::new(&[0x0300]),
+            Self::Clrc => Encoding::new(&[0x0320]),
+            Self::MovbAbsoluteToAbsolute(src, dst) => {
+                let [src_lsb, src_msb] = long(src.0);
+                let [dst_lsb, dst_msb] = long(dst.0);
+                Encoding::new(&[0x0340, src_lsb, src_msb, dst_lsb, dst_msb])
+            }
+            Self::Dint => Encoding::new(&[0x0360]),
+            Self::Abs(rd) => Encoding::new(&[0x0380 | reg(rd.0)]),
+            Self::Neg(rd) => Encoding::new(&[0x03a0 | reg(rd.0)]),
+            Self::Negb(rd) => Encoding::new(&[0x03c0 | reg(rd.0)]),
+            Self::Not(rd) => Encoding::new(&[0x03e0 | reg(rd.0)]),
+            Self::Sext(rd, f) => Encoding::new(&[0x0500 | field(f) | reg(rd.0)]),
+            Self::Zext(rd, f) => Encoding::new(&[0x0520 | field(f) | reg(rd.0)]),
+            Self::Setf(fs, fe, f) => {
+                Encoding::new(&[0x0540 | field(f) | (fe.0 as u16) << 5 | (fs.0 & 0x1f) as u16])
+            }
+            Self::MoveFieldRegToAbsolute(rs, addr, f) => {
+                let [lsb, msb] = long(addr.0);
+                Encoding::new(&[0x0580 | field(f) | reg(rs.0), lsb, msb])
+            }
+            Self::MoveFieldAbsoluteToReg(addr, rd, f) => {
+                let [lsb, msb] = long(addr.0);
+                Encoding::new(&[0x05a0 | field(f) | reg(rd.0), lsb, msb])
+            }
+            Self::MoveFieldAbsoluteToAbsolute(src, dst, f) => {
+                let [src_lsb, src_msb] = long(src.0);
+                let [dst_lsb, dst_msb] = long(dst.0);
+                Encoding::new(&[0x05c0 | field(f), src_lsb, src_msb, dst_lsb, dst_msb])
+            }
+            Self::MovbRegToAbsolute(rs, addr) => {
+                let [lsb, msb] = long(addr.0);
+                Encoding::new(&[0x05e0 | reg(rs.0), lsb, msb])
+            }
+            Self::MovbAbsoluteToReg(addr, rd) => {
+                let [lsb, msb] = long(addr.0);
+                Encoding::new(&[0x07e0 | reg(rd.0), lsb, msb])
+            }
+            Self::Trap(n) => Encoding::new(&[0x0900 | (n.0 & 0x1f) as u16]),
+            Self::Call(rs) => Encoding::new(&[0x0920 | reg(rs.0)]),
+            Self::Reti => Encoding::new(&[0x0940]),
+            Self::Rets(n) => Encoding::new(&[0x0960 | (n.0 & 0x1f) as u16]),
+            Self::Mmtm(rd, reglist) => Encoding::new(&[0x0980 | reg(rd.0), reglist.0]),
+            Self::Mmfm(rs, reglist) => Encoding::new(&[0x09a0 | reg(rs.0), reglist.0]),
+            Self::Moviw(iw, rd) => Encoding::new(&[0x09c0 | reg(rd.0), iw.0]),
+            Self::Movil(il, rd) => {
+                let [lsb, msb] = long(il.0);
+                Encoding::new(&[0x09e0 | reg(rd.0), lsb, msb])
+            }
+            Self::Addiw(iw, rd) => Encoding::new(&[0x0b00 | reg(rd.0), iw.0]),
+            Self::Addil(il, rd) => {
+                let [lsb, msb] = long(il.0);
+                Encoding::new(&[0x0b20 | reg(rd.0), lsb, msb])
+            }
+            Self::Cmpiw(iw, rd) => Encoding::new(&[0x0b40 | reg(rd.0), iw.0]),
+            Self::Cmpil(il, rd) => {
+                let [lsb, msb] = long(il.0);
+                Encoding::new(&[0x0b60 | reg(rd.0), lsb, msb])
+            }
+            Self::Andi(il, rd) => {
+                let [lsb, msb] = long(il.0);
+                Encoding::new(&[0x0b80 | reg(rd.0), lsb, msb])
+            }
+            Self::Ori(il, rd) => {
+                let [lsb, msb] = long(il.0);
+                Encoding::new(&[0x0ba0 | reg(rd.0), lsb, msb])
+            }
+            Self::Xori(il, rd) => {
+                let [lsb, msb] = long(il.0);
+                Encoding::new(&[0x0bc0 | reg(rd.0), lsb, msb])
+            }
+            Self::Subiw(iw, rd) => Encoding::new(&[0x0be0 | reg(rd.0), iw.0]),
+            Self::Subil(il, rd) => {
+                let [lsb, msb] = long(il.0);
+                Encoding::new(&[0x0d00 | reg(rd.0), lsb, msb])
+            }
+            Self::Callr(offset, _) => Encoding::new(&[0x0d3f, offset.0]),
+            Self::Calla(addr) => {
+                let [lsb, msb] = long(addr.0);
+                Encoding::new(&[0x0d5f, lsb, msb])
+            }
+            Self::Eint => Encoding::new(&[0x0d60]),
+            Self::Dsj(rd, offset, _) => Encoding::new(&[0x0d80 | reg(rd.0), offset.0]),
+            Self::Dsjeq(rd, offset, _) => Encoding::new(&[0x0da0 | reg(rd.0), offset.0]),
+            Self::Dsjne(rd, offset, _) => Encoding::new(&[0x0dc0 | reg(rd.0), offset.0]),
+            Self::Setc => Encoding::new(&[0x0de0]),
+            Self::Pixbltll => Encoding::new(&[0x0f00]),
+            Self::Pixbltlxy => Encoding::new(&[0x0f20]),
+            Self::Pixbltxyl => Encoding::new(&[0x0f40]),
+            Self::Pixbltxyxy => Encoding::new(&[0x0f60]),
+            Self::Pixbltbl => Encoding::new(&[0x0f80]),
+            Self::Pixbltbxy => Encoding::new(&[0x0fa0]),
+            Self::Filll => Encoding::new(&[0x0fc0]),
+            Self::Fillxy => Encoding::new(&[0x0fe0]),
+            // INC and DEC are ADDK/SUBK with K = 1
+            Self::Addk(kk, rd) => Encoding::new(&[0x1000 | k(kk.0) | reg(rd.0)]),
+            Self::Inc(rd) => Encoding::new(&[0x1000 | k(1) | reg(rd.0)]),
+            Self::Subk(kk, rd) => Encoding::new(&[0x1400 | k(kk.0) | reg(rd.0)]),
+            Self::Dec(rd) => Encoding::new(&[0x1400 | k(1) | reg(rd.0)]),
+            Self::Movk(kk, rd) => Encoding::new(&[0x1800 | k(kk.0) | reg(rd.0)]),
+            Self::Btstk(kk, rd) => Encoding::new(&[0x1c00 | k(kk.0) | reg(rd.0)]),
+            Self::Slak(kk, rd) => Encoding::new(&[0x2000 | k(kk.0) | reg(rd.0)]),
+            Self::Sllk(kk, rd) => Encoding::new(&[0x2400 | k(kk.0) | reg(rd.0)]),
+            Self::Srak(kk, rd) => Encoding::new(&[0x2800 | k(kk.0) | reg(rd.0)]),
+            Self::Srlk(kk, rd) => Encoding::new(&[0x2c00 | k(kk.0) | reg(rd.0)]),
+            Self::Rlk(kk, rd) => Encoding::new(&[0x3000 | k(kk.0) | reg(rd.0)]),
+            Self::Dsjs(d, rd, kk, _) => {
+                Encoding::new(&[0x3800 | (d.0 as u16) << 10 | k(kk.0) | reg(rd.0)])
+            }
+            Self::Add(rs, rd) => Encoding::new(&[0x4000 | regs(rs, rd)]),
+            Self::Addc(rs, rd) => Encoding::new(&[0x4200 | regs(rs, rd)]),
+            Self::Sub(rs, rd) => Encoding::new(&[0x4400 | regs(rs, rd)]),
+            Self::Subb(rs, rd) => Encoding::new(&[0x4600 | regs(rs, rd)]),
+            Self::Cmp(rs, rd) => Encoding::new(&[0x4800 | regs(rs, rd)]),
+            Self::Btst(rs, rd) => Encoding::new(&[0x4a00 | regs(rs, rd)]),
+            Self::MoveReg(rs, rd) => {
+                // the file bit belongs to rs, M says rd is in the other file
+                let m = ((rs.0 ^ rd.0) & 0x10) as u16;
+                let rf = (rs.0 & 0x10) as u16;
+                Encoding::new(&[0x4c00
+                    | m << 5
+                    | ((rs.0 & 0xf) as u16) << 5
+                    | rf
+                    | (rd.0 & 0xf) as u16])
+            }
+            Self::And(rs, rd) => Encoding::new(&[0x5000 | regs(rs, rd)]),
+            Self::Andn(rs, rd) => Encoding::new(&[0x5200 | regs(rs, rd)]),
+            Self::Or(rs, rd) => Encoding::new(&[0x5400 | regs(rs, rd)]),
+            Self::Xor(rs, rd) => Encoding::new(&[0x5600 | regs(rs, rd)]),
+            Self::Clr(rd) => Encoding::new(&[0x5600 | regs(Rs(rd.0), rd)]),
+            Self::Divs(rs, rd) => Encoding::new(&[0x5800 | regs(rs, rd)]),
+            Self::Divu(rs, rd) => Encoding::new(&[0x5a00 | regs(rs, rd)]),
+            Self::Mpys(rs, rd) => Encoding::new(&[0x5c00 | regs(rs, rd)]),
+            Self::Mpyu(rs, rd) => Encoding::new(&[0x5e00 | regs(rs, rd)]),
+            Self::Sla(rs, rd) => Encoding::new(&[0x6000 | regs(rs, rd)]),
+            Self::Sll(rs, rd) => Encoding::new(&[0x6200 | regs(rs, rd)]),
+            Self::Sra(rs, rd) => Encoding::new(&[0x6400 | regs(rs, rd)]),
+            Self::Srl(rs, rd) => Encoding::new(&[0x6600 | regs(rs, rd)]),
+            Self::Rl(rs, rd) => Encoding::new(&[0x6800 | regs(rs, rd)]),
+            Self::Lmo(rs, rd) => Encoding::new(&[0x6a00 | regs(rs, rd)]),
+            Self::Mods(rs, rd) => Encoding::new(&[0x6c00 | regs(rs, rd)]),
+            Self::Modu(rs, rd) => Encoding::new(&[0x6e00 | regs(rs, rd)]),
+            Self::MoveFieldRegToIndirect(rs, rd, f) => {
+                Encoding::new(&[0x8000 | field(f) | regs(rs, rd)])
+            }
+            Self::MoveFieldIndirectToReg(rs, rd, f) => {
+                Encoding::new(&[0x8400 | field(f) | regs(rs, rd)])
+            }
+            Self::MoveFieldIndirectToIndirect(rs, rd, f) => {
+                Encoding::new(&[0x8800 | field(f) | regs(rs, rd)])
+            }
+            Self::MovbRegToIndirect(rs, rd) => Encoding::new(&[0x8c00 | regs(rs, rd)]),
+            Self::MovbIndirectToReg(rs, rd) => Encoding::new(&[0x8e00 | regs(rs, rd)]),
+            Self::MoveFieldRegToIndirectPostinc(rs, rd, f) => {
+                Encoding::new(&[0x9000 | field(f) | regs(rs, rd)])
+            }
+            Self::MoveFieldIndirectPostincToReg(rs, rd, f) => {
+                Encoding::new(&[0x9400 | field(f) | regs(rs, rd)])
+            }
+            Self::MoveFieldIndirectToIndirectPostinc(rs, rd, f) => {
+                Encoding::new(&[0x9800 | field(f) | regs(rs, rd)])
+            }
+            Self::MovbIndirectToIndirect(rs, rd) => Encoding::new(&[0x9c00 | regs(rs, rd)]),
+            Self::MoveFieldRegToIndirectPredec(rs, rd, f) => {
+                Encoding::new(&[0xa000 | field(f) | regs(rs, rd)])
+            }
+            Self::MoveFieldIndirectPredecToReg(rs, rd, f) => {
+                Encoding::new(&[0xa400 | field(f) | regs(rs, rd)])
+            }
+            Self::MoveFieldIndirectToIndirectPredec(rs, rd, f) => {
+                Encoding::new(&[0xa800 | field(f) | regs(rs, rd)])
+            }
+            Self::MovbRegToIndirectOffset(rs, rd, offset) => {
+                Encoding::new(&[0xac00 | regs(rs, rd), offset.0])
+            }
+            Self::MovbIndirectOffsetToReg(rs, rd, offset) => {
+                Encoding::new(&[0xae00 | regs(rs, rd), offset.0])
+            }
+            Self::MoveFieldRegToIndirectOffset(rs, rd, f, offset) => {
+                Encoding::new(&[0xb000 | field(f) | regs(rs, rd), offset.0])
+            }
+            Self::MoveFieldIndirectOffsetToReg(rs, rd, f, offset) => {
+                Encoding::new(&[0xb400 | field(f) | regs(rs, rd), offset.0])
+            }
+            Self::MoveFieldIndirectOffsetToIndirectOffset(rs, rd, f, src, dst) => {
+                Encoding::new(&[0xb800 | field(f) | regs(rs, rd), src.0, dst.0])
+            }
+            Self::MovbIndirectOffsetToIndirectOffset(rs, rd, src, dst) => {
+                Encoding::new(&[0xbc00 | regs(rs, rd), src.0, dst.0])
+            }
+            Self::Jrs(cc, offset, _) => {
+                Encoding::new(&[0xc000 | ((cc.0 & 0xf) as u16) << 8 | offset.0 as u16])
+            }
+            Self::Jr(cc, offset, _) => {
+                Encoding::new(&[0xc000 | ((cc.0 & 0xf) as u16) << 8, offset.0])
+            }
+            Self::Ja(cc, addr) => {
+                let [lsb, msb] = long(addr.0);
+                Encoding::new(&[0xc080 | ((cc.0 & 0xf) as u16) << 8, lsb, msb])
+            }
+            Self::MoveFieldIndirectOffsetToIndirectPostinc(rs, rd, f, offset) => {
+                Encoding::new(&[0xd000 | field(f) | regs(rs, rd), offset.0])
+            }
+            Self::MoveFieldAbsoluteToIndirectPostinc(addr, rd, f) => {
+                let [lsb, msb] = long(addr.0);
+                Encoding::new(&[0xd400 | field(f) | reg(rd.0), lsb, msb])
+            }
+            Self::Exgf(rd, f) => Encoding::new(&[0xd500 | field(f) | reg(rd.0)]),
+            Self::Line(z) => Encoding::new(&[0xdf1a | (z.0 as u16) << 7]),
+            Self::Addxy(rs, rd) => Encoding::new(&[0xe000 | regs(rs, rd)]),
+            Self::Subxy(rs, rd) => Encoding::new(&[0xe200 | regs(rs, rd)]),
+            Self::Cmpxy(rs, rd) => Encoding::new(&[0xe400 | regs(rs, rd)]),
+            Self::Cpw(rs, rd) => Encoding::new(&[0xe600 | regs(rs, rd)]),
+            Self::Cvxyl(rs, rd) => Encoding::new(&[0xe800 | regs(rs, rd)]),
+            Self::Movx(rs, rd) => Encoding::new(&[0xec00 | regs(rs, rd)]),
+            Self::Movy(rs, rd) => Encoding::new(&[0xee00 | regs(rs, rd)]),
+            Self::PixtRegToIndirectxy(rs, rd) => Encoding::new(&[0xf000 | regs(rs, rd)]),
+            Self::PixtIndirectxyToReg(rs, rd) => Encoding::new(&[0xf200 | regs(rs, rd)]),
+            Self::PixtIndirectxyToIndirectxy(rs, rd) => Encoding::new(&[0xf400 | regs(rs, rd)]),
+            Self::Drav(rs, rd) => Encoding::new(&[0xf600 | regs(rs, rd)]),
+            Self::PixtRegToIndirect(rs, rd) => Encoding::new(&[0xf800 | regs(rs, rd)]),
+            Self::PixtIndirectToReg(rs, rd) => Encoding::new(&[0xfa00 | regs(rs, rd)]),
+            Self::PixtIndirectToIndirect(rs, rd) => Encoding::new(&[0xfc00 | regs(rs, rd)]),
+            Self::Dw(word) => Encoding::new(&[word.0]),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use core::fmt;
+use std::io;
+
+use crate::asm::AsmError;
+use crate::parser::ParseErrorKind;
+use crate::symbol::BitAddress;
+
+#[derive(Debug)]
+pub enum Error {
+    // the input ends partway through the instruction at this address
+    Truncated { address: BitAddress },
+    // the word at this address isn't an instruction, or only decodes with
+    // don't-care bits set
+    InvalidEncoding { address: BitAddress, word: u16 },
+    Io(io::Error),
+    // an operand that doesn't fit its field, e.g. ADDK 33, A0
+    OutOfRange(AsmError),
+    // anything else the assembler rejects, the span in the AsmError points at
+    // the offending part of the line
+    Parse(AsmError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Error::Truncated { address } => write!(
+                fmt,
+                "instruction at {} runs past the end of the input",
+                address
+            ),
+            Error::InvalidEncoding { address, word } => write!(
+                fmt,
+                "{:04X}h at {} is not a valid instruction",
+                word, address
+            ),
+            Error::Io(e) => write!(fmt, "{}", e),
+            Error::OutOfRange(e) | Error::Parse(e) => write!(fmt, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::OutOfRange(e) | Error::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<AsmError> for Error {
+    fn from(e: AsmError) -> Self {
+        match e.error.kind {
+            ParseErrorKind::OutOfRange => Error::OutOfRange(e),
+            ParseErrorKind::Syntax => Error::Parse(e),
+        }
+    }
+}
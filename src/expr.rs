@@ -0,0 +1,311 @@
+use core::ops::Range;
+
+use crate::parser::ParseError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    // !x, 1 if x is 0
+    LogicalNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    And,
+    Xor,
+    Or,
+    // comparisons and the logical operators give 1 or 0
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    LogicalAnd,
+    LogicalOr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Symbol(String),
+    // $, the bit address of the current instruction
+    Pc,
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+// loosest binding first, same precedence as C
+const LEVELS: &[&[(&str, BinaryOp)]] = &[
+    &[("||", BinaryOp::LogicalOr)],
+    &[("&&", BinaryOp::LogicalAnd)],
+    &[("|", BinaryOp::Or)],
+    &[("^", BinaryOp::Xor)],
+    &[("&", BinaryOp::And)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+    &[
+        ("<=", BinaryOp::Le),
+        (">=", BinaryOp::Ge),
+        ("<", BinaryOp::Lt),
+        (">", BinaryOp::Gt),
+    ],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Rem),
+    ],
+];
+
+pub(crate) fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+pub(crate) fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+// decimal, 0x1F, 1Fh or 101b
+pub fn parse_number(text: &str) -> Option<i64> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return i64::from_str_radix(hex, 16).ok();
+    }
+    if let Some(hex) = text.strip_suffix('h').or_else(|| text.strip_suffix('H')) {
+        return i64::from_str_radix(hex, 16).ok();
+    }
+    if let Some(bin) = text.strip_suffix('b').or_else(|| text.strip_suffix('B')) {
+        if let Ok(value) = i64::from_str_radix(bin, 2) {
+            return Some(value);
+        }
+    }
+    text.parse::<i64>().ok()
+}
+
+// parse_number() for something that has to fit in T, e.g. a fill byte or a
+// file offset given on the command line
+pub fn parse_ranged<T: TryFrom<i64>>(text: &str) -> Result<T, String> {
+    let n = parse_number(text).ok_or_else(|| format!("'{}' is not a valid number", text))?;
+    T::try_from(n).map_err(|_| format!("'{}' is out of range", text))
+}
+
+// the disassembler prints hex as {:X}h, so "FFC00000h" has to be a number
+// even though it looks like an identifier
+pub(crate) fn display_hex(text: &str) -> Option<i64> {
+    let hex = text.strip_suffix('h')?;
+    if hex.is_empty()
+        || !hex
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c))
+    {
+        return None;
+    }
+    i64::from_str_radix(hex, 16).ok()
+}
+
+struct ExprParser<'a> {
+    text: &'a str,
+    // offset of text within the source line, for error spans
+    start: usize,
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn span(&self, len: usize) -> Range<usize> {
+        self.start + self.pos..self.start + self.pos + len
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        let len = self.rest().chars().next().map_or(0, char::len_utf8);
+        ParseError::new(message, self.span(len))
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        // "<" mustn't match the start of "<<" and so on
+        let longer = LEVELS.iter().flat_map(|level| level.iter()).any(|(op, _)| {
+            op.len() > token.len() && op.starts_with(token) && self.rest().starts_with(op)
+        });
+        if self.rest().starts_with(token) && !longer {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, ParseError> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for (token, op) in LEVELS[level] {
+                if self.eat(token) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(*op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.eat("-") {
+            Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.unary()?)))
+        } else if self.eat("~") {
+            Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)))
+        } else if self.eat("!") {
+            Ok(Expr::Unary(UnaryOp::LogicalNot, Box::new(self.unary()?)))
+        } else if self.eat("+") {
+            self.unary()
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        self.skip_space();
+        let rest = self.rest();
+        let first = match rest.chars().next() {
+            Some(c) => c,
+            None => return Err(self.error("expected a value")),
+        };
+        if self.eat("(") {
+            let inner = self.binary(0)?;
+            if !self.eat(")") {
+                return Err(self.error("expected ')'"));
+            }
+            Ok(inner)
+        } else if self.eat("$") {
+            Ok(Expr::Pc)
+        } else if first == '\'' {
+            // character constant, 'A'
+            let mut chars = rest[1..].chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), Some('\'')) if c.is_ascii() => {
+                    self.pos += 3;
+                    Ok(Expr::Number(c as i64))
+                }
+                _ => Err(self.error("invalid character constant")),
+            }
+        } else if first.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            match parse_number(&rest[..len]) {
+                Some(value) => {
+                    self.pos += len;
+                    Ok(Expr::Number(value))
+                }
+                None => Err(ParseError::new(
+                    format!("invalid number '{}'", &rest[..len]),
+                    self.span(len),
+                )),
+            }
+        } else if is_ident_start(first) {
+            let len = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+            self.pos += len;
+            Ok(Expr::Symbol(rest[..len].to_string()))
+        } else {
+            Err(self.error(format!("unexpected '{}'", first)))
+        }
+    }
+}
+
+pub fn parse_expr(text: &str, start: usize) -> Result<Expr, ParseError> {
+    let mut parser = ExprParser {
+        text,
+        start,
+        pos: 0,
+    };
+    let expr = parser.binary(0)?;
+    parser.skip_space();
+    if let Some(c) = parser.rest().chars().next() {
+        return Err(parser.error(format!("unexpected '{}'", c)));
+    }
+    Ok(expr)
+}
+
+impl Expr {
+    // whether the value depends on where it's assembled
+    pub fn uses_pc(&self) -> bool {
+        match self {
+            Expr::Number(_) | Expr::Symbol(_) => false,
+            Expr::Pc => true,
+            Expr::Unary(_, inner) => inner.uses_pc(),
+            Expr::Binary(_, lhs, rhs) => lhs.uses_pc() || rhs.uses_pc(),
+        }
+    }
+
+    // Folds the expression given a way to look up symbols and the value of $
+    pub fn eval(
+        &self,
+        pc: i64,
+        symbol: &dyn Fn(&str) -> Result<i64, String>,
+    ) -> Result<i64, String> {
+        let overflow = || "arithmetic overflow".to_string();
+        match self {
+            Expr::Number(n) => Ok(*n),
+            Expr::Symbol(name) => symbol(name),
+            Expr::Pc => Ok(pc),
+            Expr::Unary(op, inner) => {
+                let value = inner.eval(pc, symbol)?;
+                match op {
+                    UnaryOp::Neg => value.checked_neg().ok_or_else(overflow),
+                    UnaryOp::Not => Ok(!value),
+                    UnaryOp::LogicalNot => Ok((value == 0) as i64),
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let a = lhs.eval(pc, symbol)?;
+                let b = rhs.eval(pc, symbol)?;
+                let shift = || {
+                    u32::try_from(b)
+                        .ok()
+                        .filter(|&b| b < 64)
+                        .ok_or_else(|| format!("invalid shift amount {}", b))
+                };
+                match op {
+                    BinaryOp::Add => a.checked_add(b).ok_or_else(overflow),
+                    BinaryOp::Sub => a.checked_sub(b).ok_or_else(overflow),
+                    BinaryOp::Mul => a.checked_mul(b).ok_or_else(overflow),
+                    BinaryOp::Div | BinaryOp::Rem if b == 0 => Err("division by zero".to_string()),
+                    BinaryOp::Div => a.checked_div(b).ok_or_else(overflow),
+                    BinaryOp::Rem => a.checked_rem(b).ok_or_else(overflow),
+                    BinaryOp::Shl => Ok(a << shift()?),
+                    BinaryOp::Shr => Ok(a >> shift()?),
+                    BinaryOp::And => Ok(a & b),
+                    BinaryOp::Xor => Ok(a ^ b),
+                    BinaryOp::Or => Ok(a | b),
+                    BinaryOp::Eq => Ok((a == b) as i64),
+                    BinaryOp::Ne => Ok((a != b) as i64),
+                    BinaryOp::Lt => Ok((a < b) as i64),
+                    BinaryOp::Le => Ok((a <= b) as i64),
+                    BinaryOp::Gt => Ok((a > b) as i64),
+                    BinaryOp::Ge => Ok((a >= b) as i64),
+                    BinaryOp::LogicalAnd => Ok((a != 0 && b != 0) as i64),
+                    BinaryOp::LogicalOr => Ok((a != 0 || b != 0) as i64),
+                }
+            }
+        }
+    }
+}
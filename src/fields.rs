@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+
+use crate::disasm::DecodedInstruction;
+use crate::instruction::Instruction;
+use crate::register::Reg;
+use crate::symbol::BitAddress;
+use crate::vectors::read_vectors;
+
+// What's known about the two fields SETF sets up, None where it depends on
+// the path taken or on something like a PUTST
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FieldState {
+    // 1 to 32, FS 0 means 32
+    pub size: [Option<u8>; 2],
+    pub sign_extend: [Option<bool>; 2],
+}
+
+impl FieldState {
+    // ST is set to 10h on reset and on every interrupt and trap
+    pub const RESET: FieldState = FieldState {
+        size: [Some(16), Some(32)],
+        sign_extend: [Some(false), Some(false)],
+    };
+
+    // whatever both paths agree on
+    fn join(self, other: FieldState) -> FieldState {
+        FieldState {
+            size: [
+                same(self.size[0], other.size[0]),
+                same(self.size[1], other.size[1]),
+            ],
+            sign_extend: [
+                same(self.sign_extend[0], other.sign_extend[0]),
+                same(self.sign_extend[1], other.sign_extend[1]),
+            ],
+        }
+    }
+
+    // the state after `instruction` runs
+    fn apply(self, instruction: &Instruction) -> FieldState {
+        let mut state = self;
+        match instruction {
+            Instruction::Setf(fs, fe, f) => {
+                let f = f.0 as usize;
+                state.size[f] = Some(if fs.0 == 0 { 32 } else { fs.0 });
+                state.sign_extend[f] = Some(fe.0);
+            }
+            // whatever was in the register
+            Instruction::Exgf(_, f) => {
+                state.size[f.0 as usize] = None;
+                state.sign_extend[f.0 as usize] = None;
+            }
+            Instruction::Putst(_) | Instruction::Popst => state = FieldState::default(),
+            _ => {}
+        }
+        state
+    }
+
+    // "8-bit zext" for an instruction that reads a field into a register,
+    // just "8-bit" when only the size matters
+    pub fn describe(&self, instruction: &Instruction) -> Option<String> {
+        let reads = instruction.reads();
+        let f = [false, true]
+            .into_iter()
+            .find(|f| reads.contains(&Reg::field_size(*f)))?;
+        let mut text = format!("{}-bit", self.size[f as usize]?);
+        if reads.contains(&Reg::field_extension(f)) {
+            match self.sign_extend[f as usize] {
+                Some(true) => text.push_str(" sext"),
+                Some(false) => text.push_str(" zext"),
+                None => {}
+            }
+        }
+        Some(text)
+    }
+}
+
+fn same<T: PartialEq>(a: Option<T>, b: Option<T>) -> Option<T> {
+    if a == b {
+        a
+    } else {
+        None
+    }
+}
+
+// The trap handlers start from the reset state, anything else from nothing
+// known. With neither the walk starts at `start`.
+pub fn field_entries(
+    words: &[u16],
+    start: BitAddress,
+    entries: &[BitAddress],
+) -> BTreeMap<BitAddress, FieldState> {
+    let mut states: BTreeMap<_, _> = entries
+        .iter()
+        .map(|entry| (*entry, FieldState::default()))
+        .collect();
+    for vector in read_vectors(words, start) {
+        states.insert(vector.handler, FieldState::RESET);
+    }
+    if states.is_empty() {
+        states.insert(start, FieldState::default());
+    }
+    states
+}
+
+// Follows the field settings from each entry through branches and into
+// calls, giving the state going into every instruction reached. A call
+// could change them, so nothing is known after one returns.
+pub fn track_fields(
+    instructions: &[DecodedInstruction],
+    entries: &BTreeMap<BitAddress, FieldState>,
+) -> BTreeMap<BitAddress, FieldState> {
+    let by_address: BTreeMap<_, _> = instructions
+        .iter()
+        .filter(|decoded| !matches!(decoded.instruction, Instruction::Dw(_)))
+        .map(|decoded| (decoded.address, decoded))
+        .collect();
+    let mut states: BTreeMap<BitAddress, FieldState> = BTreeMap::new();
+    let mut pending: Vec<_> = entries.iter().map(|(a, s)| (*a, *s)).collect();
+    while let Some((address, incoming)) = pending.pop() {
+        let decoded = match by_address.get(&address) {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+        let state = match states.get(&address) {
+            Some(old) if old.join(incoming) == *old => continue,
+            Some(old) => old.join(incoming),
+            None => incoming,
+        };
+        states.insert(address, state);
+
+        let after = state.apply(&decoded.instruction);
+        pending.extend(decoded.branch_target().map(|target| (target, after)));
+        if !decoded.is_terminator() {
+            let after = if decoded.is_call() {
+                FieldState::default()
+            } else {
+                after
+            };
+            pending.push((decoded.next_address(), after));
+        }
+    }
+    states
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use crate::disasm::{to_words, trailing_byte, DecodedInstruction, Decoder};
+use crate::instruction::Instruction;
+use crate::symbol::{BitAddress, IW};
+use crate::vectors::{read_vector, read_vectors};
+
+// index into words of the word at `address`, if it's in there
+fn word_index(words: &[u16], start: BitAddress, address: BitAddress) -> Option<usize> {
+    let offset = address.0.wrapping_sub(start.0);
+    let pos = (offset / 16) as usize;
+    (offset.is_multiple_of(16) && pos < words.len()).then_some(pos)
+}
+
+// Follows execution from each entry point, through both sides of conditional
+// branches and into calls and traps, until it hits a return, an unconditional
+// jump, something that isn't an instruction or the edge of the buffer.
+// Returns every instruction reached by its address.
+pub fn trace(
+    words: &[u16],
+    start: BitAddress,
+    entries: &[BitAddress],
+) -> BTreeMap<BitAddress, DecodedInstruction> {
+    let mut code = BTreeMap::new();
+    // words taken by an instruction already found
+    let mut covered = vec![false; words.len()];
+    // popped from the end, so reverse them to take the entries in order
+    let mut pending: Vec<_> = entries.iter().rev().copied().collect();
+    while let Some(mut address) = pending.pop() {
+        while let Some(pos) = word_index(words, start, address) {
+            if code.contains_key(&address) {
+                break;
+            }
+            let (instruction, len) = match Decoder::decode(&words[pos..], address) {
+                Ok(decoded) => decoded,
+                Err(_) => break,
+            };
+            // landing in the middle of another instruction, one of the two
+            // paths has to be wrong so keep the first
+            if covered[pos..pos + len].contains(&true) {
+                break;
+            }
+            covered[pos..pos + len].fill(true);
+            let decoded = DecodedInstruction::new(address, instruction, &words[pos..pos + len]);
+            code.insert(address, decoded);
+
+            pending.extend(decoded.branch_target());
+            if let Instruction::Trap(n) = instruction {
+                pending.extend(read_vector(words, start, n.0).map(|vector| vector.handler));
+            }
+            if decoded.is_terminator() {
+                break;
+            }
+            address = decoded.next_address();
+        }
+    }
+    code
+}
+
+// `entries` and the trap handlers, or just `start` if there are neither
+pub fn flow_entries(words: &[u16], start: BitAddress, entries: &[BitAddress]) -> Vec<BitAddress> {
+    let mut entries = entries.to_vec();
+    entries.extend(
+        read_vectors(words, start)
+            .iter()
+            .map(|vector| vector.handler),
+    );
+    if entries.is_empty() {
+        entries.push(start);
+    }
+    entries
+}
+
+// Disassembles only what can be reached from flow_entries(), everything else
+// comes out as DW
+pub fn disassemble_flow(
+    bytebuf: &[u8],
+    start: BitAddress,
+    entries: &[BitAddress],
+) -> Vec<DecodedInstruction> {
+    let (words, odd_byte) = to_words(bytebuf);
+    let code = trace(&words, start, &flow_entries(&words, start, entries));
+
+    let mut inst_vec = vec![];
+    let mut pos = 0;
+    while pos < words.len() {
+        let address = start.offset_words(pos as i64);
+        let decoded = match code.get(&address) {
+            Some(decoded) => *decoded,
+            None => DecodedInstruction::new(
+                address,
+                Instruction::Dw(IW(words[pos])),
+                &words[pos..pos + 1],
+            ),
+        };
+        pos += decoded.words().len();
+        inst_vec.push(decoded);
+    }
+    if let Some(byte) = odd_byte {
+        inst_vec.push(trailing_byte(start.offset_words(words.len() as i64), byte));
+    }
+    inst_vec
+}
@@ -1,6 +1,9 @@
+use std::collections::BTreeSet;
+
+use crate::register::Reg;
 use crate::symbol::{
-    Address, Condition, /*M,*/ Offset, Offset8, Rd, RegList, Rs, D, F, FE, FS, IL, IW, K, N,
-    PC, Z,
+    Address, BitAddress, Condition, /*M,*/ Offset, Offset8, Rd, RegList, Rs, D, F, FE, FS, IL,
+    IW, K, N, PC, Z,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -16,7 +19,8 @@ pub enum Instruction {
     And(Rs, Rd),
     Andi(IL, Rd),
     Andn(Rs, Rd),
-    //Andni(IL, Rd),  somehow this is an alias of andi?
+    // ANDNI IL, Rd is the same opcode with the immediate stored as written,
+    // it's parsed into Andi rather than getting a variant of its own
     Btstk(K, Rd),
     Btst(Rs, Rd),
     Clr(Rd),
@@ -125,9 +129,9 @@ pub enum Instruction {
     Setf(FS, FE, F),
     Trap(N),
     // Jump
-    Dsj(Rd, Offset),
-    Dsjeq(Rd, Offset),
-    Dsjne(Rd, Offset),
+    Dsj(Rd, Offset, PC),
+    Dsjeq(Rd, Offset, PC),
+    Dsjne(Rd, Offset, PC),
     Dsjs(D, Rd, K, PC), // the manual calls this offset rather than K but it's in the position of K and 5 bits long
     // it's also in with other K instructions
     Ja(Condition, Address),
@@ -262,9 +266,9 @@ impl Instruction {
             Self::Rev(_) => "REV",
             Self::Setf(_, _, _) => "SETF",
             Self::Trap(_) => "TRAP",
-            Self::Dsj(_, _) => "DSJ",
-            Self::Dsjeq(_, _) => "DSJEQ",
-            Self::Dsjne(_, _) => "DSJNE",
+            Self::Dsj(_, _, _) => "DSJ",
+            Self::Dsjeq(_, _, _) => "DSJEQ",
+            Self::Dsjne(_, _, _) => "DSJNE",
             Self::Dsjs(_, _, _, _) => "DSJS",
             Self::Ja(_, _) => "JA",
             Self::Jr(_, _, _) => "JR",
@@ -284,3 +288,308 @@ impl Instruction {
         }
     }
 }
+
+impl Instruction {
+    // Where a branch or call goes, None for anything else and for jumps
+    // through a register
+    pub fn branch_target(&self) -> Option<BitAddress> {
+        // relative branches count words from the end of the instruction
+        match self {
+            Self::Jrs(_, off8, pc) => Some(pc.0.offset_words(off8.0 as i8 as i64 + 1)),
+            Self::Jr(_, offset, pc)
+            | Self::Callr(offset, pc)
+            | Self::Dsj(_, offset, pc)
+            | Self::Dsjeq(_, offset, pc)
+            | Self::Dsjne(_, offset, pc) => Some(pc.0.offset_words(offset.0 as i16 as i64 + 2)),
+            Self::Dsjs(d, _, k, pc) if d.0 => Some(pc.0.offset_words(1 - k.0 as i64)),
+            Self::Dsjs(_, _, k, pc) => Some(pc.0.offset_words(1 + k.0 as i64)),
+            Self::Ja(_, address) | Self::Calla(address) => Some(BitAddress(address.0)),
+            _ => None,
+        }
+    }
+
+    // Execution never carries on to the next instruction: unconditional
+    // jumps and returns. Calls and traps come back so they don't count.
+    pub fn is_terminator(&self) -> bool {
+        match self {
+            Self::Jr(condition, _, _) | Self::Jrs(condition, _, _) | Self::Ja(condition, _) => {
+                condition.0 == 0
+            }
+            Self::Jump(_) | Self::Rets(_) | Self::Reti => true,
+            _ => false,
+        }
+    }
+
+    // The @address operands, in the order they're written
+    pub fn absolute_addresses(&self) -> Vec<BitAddress> {
+        match self {
+            Self::MovbRegToAbsolute(_, address)
+            | Self::MovbAbsoluteToReg(address, _)
+            | Self::MoveFieldRegToAbsolute(_, address, _)
+            | Self::MoveFieldAbsoluteToReg(address, _, _)
+            | Self::MoveFieldAbsoluteToIndirectPostinc(address, _, _) => {
+                vec![BitAddress(address.0)]
+            }
+            Self::MovbAbsoluteToAbsolute(src, dst)
+            | Self::MoveFieldAbsoluteToAbsolute(src, dst, _) => {
+                vec![BitAddress(src.0), BitAddress(dst.0)]
+            }
+            _ => vec![],
+        }
+    }
+
+    // The B registers the graphics instructions use without naming them, see
+    // the user's guide for each. PIXBLT and FILL leave B10-B14 trashed.
+    pub fn implicit_reads(&self) -> BTreeSet<Reg> {
+        let b: &[u8] = match self {
+            Self::Pixbltll => &[0, 1, 2, 3, 7],
+            Self::Pixbltlxy => &[0, 1, 2, 3, 4, 5, 6, 7],
+            Self::Pixbltxyl => &[0, 1, 2, 3, 4, 7],
+            Self::Pixbltxyxy => &[0, 1, 2, 3, 4, 5, 6, 7],
+            Self::Pixbltbl => &[0, 1, 2, 3, 7, 8, 9],
+            Self::Pixbltbxy => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+            Self::Filll => &[2, 3, 7, 9],
+            Self::Fillxy => &[2, 3, 4, 5, 6, 7, 9],
+            Self::Line(_) => &[0, 2, 4, 5, 6, 7, 9, 10, 11, 12],
+            Self::Drav(..) => &[4, 5, 6, 9],
+            Self::Cpw(..) => &[5, 6],
+            Self::Cvxyl(..) | Self::PixtIndirectxyToReg(..) => &[4],
+            Self::PixtRegToIndirectxy(..) | Self::PixtIndirectxyToIndirectxy(..) => &[4, 5, 6],
+            _ => &[],
+        };
+        b.iter().map(|n| Reg::B(*n)).collect()
+    }
+
+    pub fn implicit_writes(&self) -> BTreeSet<Reg> {
+        let b: &[u8] = match self {
+            Self::Pixbltll | Self::Pixbltxyl | Self::Pixbltbl => &[0, 2, 10, 11, 12, 13, 14],
+            // window clipping adjusts the destination and size
+            Self::Pixbltlxy | Self::Pixbltxyxy | Self::Pixbltbxy => &[0, 2, 7, 10, 11, 12, 13, 14],
+            Self::Filll => &[2, 10, 11, 12, 13, 14],
+            Self::Fillxy => &[2, 7, 10, 11, 12, 13, 14],
+            Self::Line(_) => &[0, 2, 10, 14],
+            _ => &[],
+        };
+        b.iter().map(|n| Reg::B(*n)).collect()
+    }
+
+    // Pushes the return address, so whatever it goes to is a subroutine
+    pub fn is_call(&self) -> bool {
+        matches!(self, Self::Call(_) | Self::Callr(..) | Self::Calla(_))
+    }
+
+    // Every register, status bit and field setting the instruction depends
+    // on, including the implicit B registers. Memory and the I/O registers
+    // aren't tracked, and neither is the PC stepping to the next instruction,
+    // only PC-relative branches and the like read it.
+    pub fn reads(&self) -> BTreeSet<Reg> {
+        let mut reads = self.implicit_reads();
+        reads.extend(self.accesses().0);
+        reads
+    }
+
+    // Everything the instruction changes, the same way as reads()
+    pub fn writes(&self) -> BTreeSet<Reg> {
+        let mut writes = self.implicit_writes();
+        writes.extend(self.accesses().1);
+        writes
+    }
+
+    fn accesses(&self) -> (Vec<Reg>, Vec<Reg>) {
+        use Reg::{Pc, Sp, C, N, V, Z};
+        let r = Reg::from_number;
+        // 64 bit results go in an even Rd and the one after it
+        let pair = |rd: &Rd| {
+            if rd.0.is_multiple_of(2) {
+                vec![r(rd.0), r(rd.0 + 1)]
+            } else {
+                vec![r(rd.0)]
+            }
+        };
+        let fs = |f: &F| Reg::field_size(f.0);
+        let fe = |f: &F| Reg::field_extension(f.0);
+        let status = crate::register::STATUS.to_vec();
+
+        match self {
+            Self::Abs(rd) => (vec![r(rd.0)], vec![r(rd.0), N, Z, V]),
+            Self::Add(rs, rd) | Self::Sub(rs, rd) | Self::Addxy(rs, rd) | Self::Subxy(rs, rd) => {
+                (vec![r(rs.0), r(rd.0)], vec![r(rd.0), N, C, Z, V])
+            }
+            Self::Addc(rs, rd) | Self::Subb(rs, rd) => {
+                (vec![r(rs.0), r(rd.0), C], vec![r(rd.0), N, C, Z, V])
+            }
+            Self::Addiw(_, rd)
+            | Self::Addil(_, rd)
+            | Self::Addk(_, rd)
+            | Self::Subiw(_, rd)
+            | Self::Subil(_, rd)
+            | Self::Subk(_, rd)
+            | Self::Dec(rd)
+            | Self::Inc(rd)
+            | Self::Neg(rd)
+            | Self::Slak(_, rd) => (vec![r(rd.0)], vec![r(rd.0), N, C, Z, V]),
+            Self::Negb(rd) => (vec![r(rd.0), C], vec![r(rd.0), N, C, Z, V]),
+            Self::And(rs, rd) | Self::Andn(rs, rd) | Self::Or(rs, rd) | Self::Xor(rs, rd) => {
+                (vec![r(rs.0), r(rd.0)], vec![r(rd.0), Z])
+            }
+            Self::Andi(_, rd) | Self::Ori(_, rd) | Self::Xori(_, rd) | Self::Not(rd) => {
+                (vec![r(rd.0)], vec![r(rd.0), Z])
+            }
+            Self::Btstk(_, rd) => (vec![r(rd.0)], vec![Z]),
+            Self::Btst(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![Z]),
+            // XOR Rd, Rd, the old value doesn't matter
+            Self::Clr(rd) => (vec![], vec![r(rd.0), Z]),
+            Self::Clrc | Self::Setc => (vec![], vec![C]),
+            Self::Cmp(rs, rd) | Self::Cmpxy(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![N, C, Z, V]),
+            Self::Cmpiw(_, rd) | Self::Cmpil(_, rd) => (vec![r(rd.0)], vec![N, C, Z, V]),
+            Self::Divs(rs, rd) | Self::Divu(rs, rd) => {
+                let mut reads = pair(rd);
+                reads.push(r(rs.0));
+                let mut writes = pair(rd);
+                writes.extend([Z, V]);
+                if matches!(self, Self::Divs(..)) {
+                    writes.push(N);
+                }
+                (reads, writes)
+            }
+            Self::Mods(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![r(rd.0), N, Z, V]),
+            Self::Modu(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![r(rd.0), Z, V]),
+            // FS1 is the size of the multiplier in Rs
+            Self::Mpys(rs, rd) => (
+                vec![r(rs.0), r(rd.0), Reg::Fs1],
+                [pair(rd), vec![N, Z]].concat(),
+            ),
+            Self::Mpyu(rs, rd) => (
+                vec![r(rs.0), r(rd.0), Reg::Fs1],
+                [pair(rd), vec![Z]].concat(),
+            ),
+            Self::Lmo(rs, rd) => (vec![r(rs.0)], vec![r(rd.0), Z]),
+            Self::Sext(rd, f) => (vec![r(rd.0), fs(f)], vec![r(rd.0), N, Z]),
+            Self::Zext(rd, f) => (vec![r(rd.0), fs(f)], vec![r(rd.0), Z]),
+
+            Self::MovbRegToIndirect(rs, rd)
+            | Self::MovbIndirectToIndirect(rs, rd)
+            | Self::MovbRegToIndirectOffset(rs, rd, _)
+            | Self::MovbIndirectOffsetToIndirectOffset(rs, rd, ..) => {
+                (vec![r(rs.0), r(rd.0)], vec![])
+            }
+            Self::MovbIndirectToReg(rs, rd) | Self::MovbIndirectOffsetToReg(rs, rd, _) => {
+                (vec![r(rs.0)], vec![r(rd.0), N, Z, V])
+            }
+            Self::MovbRegToAbsolute(rs, _) => (vec![r(rs.0)], vec![]),
+            Self::MovbAbsoluteToReg(_, rd) => (vec![], vec![r(rd.0), N, Z, V]),
+            Self::MovbAbsoluteToAbsolute(..) => (vec![], vec![]),
+            Self::MoveReg(rs, rd) => (vec![r(rs.0)], vec![r(rd.0), N, Z, V]),
+            // writing a field only needs its size, reading one into a
+            // register extends it as well
+            Self::MoveFieldRegToIndirect(rs, rd, f)
+            | Self::MoveFieldIndirectToIndirect(rs, rd, f)
+            | Self::MoveFieldRegToIndirectOffset(rs, rd, f, _)
+            | Self::MoveFieldIndirectOffsetToIndirectOffset(rs, rd, f, ..) => {
+                (vec![r(rs.0), r(rd.0), fs(f)], vec![])
+            }
+            Self::MoveFieldRegToIndirectPredec(rs, rd, f)
+            | Self::MoveFieldRegToIndirectPostinc(rs, rd, f)
+            | Self::MoveFieldIndirectOffsetToIndirectPostinc(rs, rd, f, _) => {
+                (vec![r(rs.0), r(rd.0), fs(f)], vec![r(rd.0)])
+            }
+            Self::MoveFieldIndirectToReg(rs, rd, f)
+            | Self::MoveFieldIndirectOffsetToReg(rs, rd, f, _) => {
+                (vec![r(rs.0), fs(f), fe(f)], vec![r(rd.0), N, Z, V])
+            }
+            Self::MoveFieldIndirectPredecToReg(rs, rd, f)
+            | Self::MoveFieldIndirectPostincToReg(rs, rd, f) => {
+                (vec![r(rs.0), fs(f), fe(f)], vec![r(rs.0), r(rd.0), N, Z, V])
+            }
+            Self::MoveFieldIndirectToIndirectPredec(rs, rd, f)
+            | Self::MoveFieldIndirectToIndirectPostinc(rs, rd, f) => {
+                (vec![r(rs.0), r(rd.0), fs(f)], vec![r(rs.0), r(rd.0)])
+            }
+            Self::MoveFieldRegToAbsolute(rs, _, f) => (vec![r(rs.0), fs(f)], vec![]),
+            Self::MoveFieldAbsoluteToReg(_, rd, f) => (vec![fs(f), fe(f)], vec![r(rd.0), N, Z, V]),
+            Self::MoveFieldAbsoluteToIndirectPostinc(_, rd, f) => {
+                (vec![r(rd.0), fs(f)], vec![r(rd.0)])
+            }
+            Self::MoveFieldAbsoluteToAbsolute(.., f) => (vec![fs(f)], vec![]),
+            Self::Moviw(_, rd) | Self::Movil(_, rd) => (vec![], vec![r(rd.0), N, Z, V]),
+            Self::Movk(_, rd) => (vec![], vec![r(rd.0)]),
+            // only half of Rd changes
+            Self::Movx(rs, rd) | Self::Movy(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![r(rd.0)]),
+            // the list is in the same file as the pointer, see reglist_str()
+            Self::Mmtm(rd, reglist) => {
+                let mut reads = reglist_regs(rd.0, reglist.0.reverse_bits());
+                reads.push(r(rd.0));
+                (reads, vec![r(rd.0)])
+            }
+            Self::Mmfm(rs, reglist) => {
+                let mut writes = reglist_regs(rs.0, reglist.0);
+                writes.push(r(rs.0));
+                (vec![r(rs.0)], writes)
+            }
+
+            // window checks report through V
+            Self::Cpw(rs, rd) => (vec![r(rs.0)], vec![r(rd.0), V]),
+            Self::Cvxyl(rs, rd) => (vec![r(rs.0)], vec![r(rd.0)]),
+            Self::Drav(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![r(rd.0), V]),
+            Self::Fillxy | Self::Pixbltlxy | Self::Pixbltxyxy | Self::Pixbltbxy | Self::Line(_) => {
+                (vec![], vec![V])
+            }
+            Self::Filll | Self::Pixbltll | Self::Pixbltxyl | Self::Pixbltbl => (vec![], vec![]),
+            Self::PixtRegToIndirect(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![]),
+            Self::PixtRegToIndirectxy(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![V]),
+            Self::PixtIndirectToReg(rs, rd) | Self::PixtIndirectxyToReg(rs, rd) => {
+                (vec![r(rs.0)], vec![r(rd.0), N, Z, V])
+            }
+            Self::PixtIndirectToIndirect(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![]),
+            Self::PixtIndirectxyToIndirectxy(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![V]),
+
+            // calls push the address of the next instruction
+            Self::Call(rs) => (vec![r(rs.0), Sp, Pc], vec![Sp, Pc]),
+            Self::Calla(_) | Self::Callr(..) => (vec![Sp, Pc], vec![Sp, Pc]),
+            Self::Dint | Self::Eint | Self::Emu | Self::Nop | Self::Dw(_) => (vec![], vec![]),
+            Self::Exgf(rd, f) => (vec![r(rd.0), fs(f), fe(f)], vec![r(rd.0), fs(f), fe(f)]),
+            Self::Exgpc(rd, _) => (vec![r(rd.0), Pc], vec![r(rd.0), Pc]),
+            Self::Getpc(rd) => (vec![Pc], vec![r(rd.0)]),
+            Self::Getst(rd) => (status, vec![r(rd.0)]),
+            Self::Popst => (vec![Sp], [status, vec![Sp]].concat()),
+            Self::Pushst => ([status, vec![Sp]].concat(), vec![Sp]),
+            Self::Putst(rs) => (vec![r(rs.0)], status),
+            Self::Reti => (vec![Sp], [status, vec![Sp, Pc]].concat()),
+            Self::Rets(_) => (vec![Sp], vec![Sp, Pc]),
+            Self::Rev(rd) => (vec![], vec![r(rd.0)]),
+            Self::Setf(_, _, f) => (vec![], vec![fs(f), fe(f)]),
+            Self::Trap(_) => ([status, vec![Sp, Pc]].concat(), vec![Sp, Pc]),
+
+            Self::Dsj(rd, ..) | Self::Dsjs(_, rd, ..) => (vec![r(rd.0), Pc], vec![r(rd.0), Pc]),
+            Self::Dsjeq(rd, ..) | Self::Dsjne(rd, ..) => (vec![r(rd.0), Z, Pc], vec![r(rd.0), Pc]),
+            Self::Ja(condition, _) => (Reg::condition_flags(condition.0).to_vec(), vec![Pc]),
+            Self::Jr(condition, ..) => (
+                [Reg::condition_flags(condition.0), &[Pc]].concat(),
+                vec![Pc],
+            ),
+            Self::Jrs(condition, ..) => (
+                [Reg::condition_flags(condition.0), &[Pc]].concat(),
+                vec![Pc],
+            ),
+            Self::Jump(rs) => (vec![r(rs.0)], vec![Pc]),
+
+            Self::Rlk(_, rd) | Self::Sllk(_, rd) | Self::Srlk(_, rd) => {
+                (vec![r(rd.0)], vec![r(rd.0), C, Z])
+            }
+            Self::Rl(rs, rd) | Self::Sll(rs, rd) | Self::Srl(rs, rd) => {
+                (vec![r(rs.0), r(rd.0)], vec![r(rd.0), C, Z])
+            }
+            Self::Sla(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![r(rd.0), N, C, Z, V]),
+            Self::Srak(_, rd) => (vec![r(rd.0)], vec![r(rd.0), N, C, Z]),
+            Self::Sra(rs, rd) => (vec![r(rs.0), r(rd.0)], vec![r(rd.0), N, C, Z]),
+        }
+    }
+}
+
+// Bit n of an MMFM style mask is register n of the pointer's file
+fn reglist_regs(pointer: u8, list: u16) -> Vec<Reg> {
+    (0..16)
+        .filter(|n| list & 1 << n != 0)
+        .map(|n| Reg::from_number(pointer & 0x10 | n))
+        .collect()
+}
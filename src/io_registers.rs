@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+
+use crate::symbol::BitAddress;
+
+// The on-chip I/O registers are 16 bits each, from C0000000h up
+pub const IO_BASE: BitAddress = BitAddress(0xc000_0000);
+
+// In address order, None for the reserved ones
+const NAMES: [Option<&str>; 32] = [
+    Some("HESYNC"),
+    Some("HEBLNK"),
+    Some("HSBLNK"),
+    Some("HTOTAL"),
+    Some("VESYNC"),
+    Some("VEBLNK"),
+    Some("VSBLNK"),
+    Some("VTOTAL"),
+    Some("DPYCTL"),
+    Some("DPYSTRT"),
+    Some("DPYINT"),
+    Some("CONTROL"),
+    Some("HSTDATA"),
+    Some("HSTADRL"),
+    Some("HSTADRH"),
+    Some("HSTCTLL"),
+    Some("HSTCTLH"),
+    Some("INTENB"),
+    Some("INTPEND"),
+    Some("CONVSP"),
+    Some("CONVDP"),
+    Some("PSIZE"),
+    Some("PMASK"),
+    None,
+    None,
+    None,
+    None,
+    Some("HCOUNT"),
+    Some("VCOUNT"),
+    Some("DPYADR"),
+    Some("REFCNT"),
+    None,
+];
+
+// Every register with its address
+pub fn io_registers() -> impl Iterator<Item = (&'static str, BitAddress)> {
+    NAMES.iter().enumerate().filter_map(|(i, name)| {
+        let name = (*name)?;
+        Some((name, IO_BASE.offset_words(i as i64)))
+    })
+}
+
+// The register at `address`, which has to be its first bit
+pub fn io_register_name(address: BitAddress) -> Option<&'static str> {
+    io_registers()
+        .find(|(_, register)| *register == address)
+        .map(|(name, _)| name)
+}
+
+pub fn io_register_address(name: &str) -> Option<BitAddress> {
+    io_registers()
+        .find(|(register, _)| register.eq_ignore_ascii_case(name))
+        .map(|(_, address)| address)
+}
+
+// Labels for disasm::disassemble_stage2_labeled()
+pub fn io_register_labels() -> BTreeMap<BitAddress, String> {
+    io_registers()
+        .map(|(name, address)| (address, name.to_string()))
+        .collect()
+}
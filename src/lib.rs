@@ -1,3 +1,19 @@
+pub mod asm;
+pub mod callgraph;
+pub mod cfg;
 pub mod disasm;
+pub mod encode;
+mod error;
+pub mod expr;
+pub mod fields;
+pub mod flow;
 pub mod instruction;
+pub mod io_registers;
+pub mod parser;
+mod preprocess;
+pub mod register;
 pub mod symbol;
+pub mod symbol_file;
+pub mod vectors;
+
+pub use error::Error;
@@ -0,0 +1,1020 @@
+use core::fmt;
+use core::ops::Range;
+use std::collections::HashMap;
+
+use crate::expr::{display_hex, is_ident_char, is_ident_start, parse_expr, Expr};
+use crate::instruction::Instruction;
+use crate::symbol::{
+    Address, BitAddress, Condition, Offset, Offset8, Rd, RegList, Rs, D, F, FE, FS, IL, IW, K, N,
+    PC, Z,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    Syntax,
+    // the operand is well formed but its value doesn't fit the field
+    OutOfRange,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub message: String,
+    // byte range within the source line
+    pub span: Range<usize>,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>, span: Range<usize>) -> Self {
+        ParseError {
+            message: message.into(),
+            span,
+            kind: ParseErrorKind::Syntax,
+        }
+    }
+
+    pub fn out_of_range(message: impl Into<String>, span: Range<usize>) -> Self {
+        ParseError {
+            message: message.into(),
+            span,
+            kind: ParseErrorKind::OutOfRange,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{} (column {})", self.message, self.span.start + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegFile {
+    A,
+    B,
+}
+
+// SP lives in both files, so a bare "SP" has no file until it is paired up
+// with the other registers of the instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Register {
+    pub file: Option<RegFile>,
+    pub number: u8,
+}
+
+impl Register {
+    fn code(&self, file: RegFile) -> u8 {
+        match self.file.unwrap_or(file) {
+            RegFile::A => self.number,
+            RegFile::B => self.number | 0x10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperandKind {
+    Register(Register),
+    Indirect(Register),
+    IndirectPostinc(Register),
+    IndirectPredec(Register),
+    IndirectOffset(Register, Expr),
+    IndirectXY(Register),
+    Absolute(Expr),
+    Immediate(Expr),
+    // quoted text, only used by directives like .string
+    String(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Operand {
+    pub kind: OperandKind,
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Statement {
+    pub label: Option<String>,
+    pub label_span: Range<usize>,
+    pub mnemonic: Option<String>,
+    pub mnemonic_span: Range<usize>,
+    pub operands: Vec<Operand>,
+}
+
+// Everything the operands of a statement may refer to. pc is the address of
+// the instruction being built.
+#[derive(Clone, Copy)]
+pub struct Context<'a> {
+    pub pc: BitAddress,
+    pub symbols: &'a HashMap<String, i64>,
+    // always pick the long form of JR and of immediates without a W/L suffix
+    pub long: bool,
+    // treat undefined symbols as forward references to the current address
+    // instead of failing, used while the assembler is still sizing things
+    pub forward: bool,
+}
+
+pub(crate) fn parse_register(name: &str) -> Option<Register> {
+    let upper = name.to_ascii_uppercase();
+    if upper == "SP" {
+        return Some(Register {
+            file: None,
+            number: 15,
+        });
+    }
+    let file = match upper.chars().next()? {
+        'A' => RegFile::A,
+        'B' => RegFile::B,
+        _ => return None,
+    };
+    let digits = &upper[1..];
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let number = digits.parse::<u8>().ok()?;
+    if number > 15 {
+        return None;
+    }
+    Some(Register {
+        file: Some(file),
+        number,
+    })
+}
+
+fn parse_operand(text: &str, start: usize) -> Result<Operand, ParseError> {
+    let span = start..start + text.len();
+    let kind = if let Some(rest) = text.strip_prefix('"') {
+        match rest.strip_suffix('"') {
+            Some(inner) => OperandKind::String(inner.replace("\"\"", "\"")),
+            None => return Err(ParseError::new("unterminated string", span)),
+        }
+    } else if let Some(rest) = text.strip_prefix('@') {
+        OperandKind::Absolute(parse_expr(rest.trim(), start + 1)?)
+    } else if let Some(rest) = text.strip_prefix("-*") {
+        match parse_register(rest.trim()) {
+            Some(reg) => OperandKind::IndirectPredec(reg),
+            None => return Err(ParseError::new("expected a register after '-*'", span)),
+        }
+    } else if let Some(rest) = text.strip_prefix('*') {
+        let rest = rest.trim_start();
+        let name_len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let reg = match parse_register(&rest[..name_len]) {
+            Some(reg) => reg,
+            None => return Err(ParseError::new("expected a register after '*'", span)),
+        };
+        let suffix = rest[name_len..].trim();
+        if suffix.is_empty() {
+            OperandKind::Indirect(reg)
+        } else if suffix == "+" {
+            OperandKind::IndirectPostinc(reg)
+        } else if suffix.eq_ignore_ascii_case(".XY") {
+            OperandKind::IndirectXY(reg)
+        } else if let Some(inner) = suffix.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            let inner_start = start + text.len() - suffix.len() + 1;
+            let trimmed = inner.trim_start();
+            let value = parse_expr(
+                trimmed.trim_end(),
+                inner_start + inner.len() - trimmed.len(),
+            )?;
+            OperandKind::IndirectOffset(reg, value)
+        } else {
+            return Err(ParseError::new(
+                format!("unexpected '{}' after register", suffix),
+                span,
+            ));
+        }
+    } else if let Some(reg) = parse_register(text) {
+        OperandKind::Register(reg)
+    } else {
+        OperandKind::Immediate(parse_expr(text, start)?)
+    };
+    Ok(Operand { kind, span })
+}
+
+// splits on commas that aren't inside parentheses or quotes, returning each
+// piece trimmed along with its offset in the line
+pub(crate) fn split_operands(text: &str, start: usize) -> Vec<(&str, usize)> {
+    let mut pieces = vec![];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut piece_start = 0;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                pieces.push((piece_start, i));
+                piece_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    pieces.push((piece_start, text.len()));
+    pieces
+        .into_iter()
+        .map(|(from, to)| {
+            let piece = &text[from..to];
+            let trimmed = piece.trim_start();
+            (
+                trimmed.trim_end(),
+                start + from + piece.len() - trimmed.len(),
+            )
+        })
+        .collect()
+}
+
+// index of the first ';' that isn't inside a quoted string
+pub(crate) fn comment_start(line: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, ';') => return i,
+            _ => {}
+        }
+    }
+    line.len()
+}
+
+// The pieces of a line before any operand is parsed, which is as far as the
+// macro preprocessor looks
+pub(crate) struct RawLine<'a> {
+    pub label: Option<(&'a str, Range<usize>)>,
+    pub mnemonic: Option<(&'a str, Range<usize>)>,
+    // operand text and its offset in the line
+    pub operands: (&'a str, usize),
+}
+
+// directives whose label doesn't need a colon, as in "SIZE .set 8"
+const NAMING_DIRECTIVES: &[&str] = &[".set", ".equ", ".macro"];
+
+pub(crate) fn split_line(line: &str) -> RawLine<'_> {
+    let mut raw = RawLine {
+        label: None,
+        mnemonic: None,
+        operands: ("", line.len()),
+    };
+    // TI style whole line comment
+    if line.starts_with('*') {
+        return raw;
+    }
+    let code = &line[..comment_start(line)];
+
+    let mut pos = code.len() - code.trim_start().len();
+    let rest = &code[pos..];
+    if rest.starts_with(is_ident_start) {
+        let ident_len = rest.find(|c: char| !is_ident_char(c)).unwrap_or(rest.len());
+        let after = &rest[ident_len..];
+        let next_word = after.trim_start().split(char::is_whitespace).next();
+        if after.starts_with(':') {
+            raw.label = Some((&rest[..ident_len], pos..pos + ident_len));
+            pos += ident_len + 1;
+        } else if after.starts_with(char::is_whitespace)
+            && next_word.is_some_and(|word| {
+                NAMING_DIRECTIVES
+                    .iter()
+                    .any(|directive| word.eq_ignore_ascii_case(directive))
+            })
+        {
+            raw.label = Some((&rest[..ident_len], pos..pos + ident_len));
+            pos += ident_len;
+        }
+    }
+
+    let rest = &code[pos..];
+    pos += rest.len() - rest.trim_start().len();
+    let rest = &code[pos..];
+    if rest.trim_end().is_empty() {
+        return raw;
+    }
+    let mnemonic_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    raw.mnemonic = Some((&rest[..mnemonic_len], pos..pos + mnemonic_len));
+    pos += mnemonic_len;
+    raw.operands = (&code[pos..], pos);
+    raw
+}
+
+pub fn parse_line(line: &str) -> Result<Statement, ParseError> {
+    let raw = split_line(line);
+    let mut statement = Statement::default();
+    if let Some((label, span)) = raw.label {
+        statement.label = Some(label.to_string());
+        statement.label_span = span;
+    }
+    if let Some((mnemonic, span)) = raw.mnemonic {
+        statement.mnemonic = Some(mnemonic.to_string());
+        statement.mnemonic_span = span;
+    }
+
+    let (operand_text, pos) = raw.operands;
+    if operand_text.trim().is_empty() {
+        return Ok(statement);
+    }
+    for (text, start) in split_operands(operand_text, pos) {
+        if text.is_empty() {
+            return Err(ParseError::new("missing operand", start..start));
+        }
+        let operand = parse_operand(text, start)?;
+        // the disassembler prints PIXT's *Rd.XY as "*Rd, XY"
+        if let OperandKind::Immediate(Expr::Symbol(sym)) = &operand.kind {
+            if sym.eq_ignore_ascii_case("XY") {
+                if let Some(prev) = statement.operands.last_mut() {
+                    if let OperandKind::Indirect(reg) = prev.kind {
+                        prev.kind = OperandKind::IndirectXY(reg);
+                        prev.span.end = operand.span.end;
+                        continue;
+                    }
+                }
+            }
+        }
+        statement.operands.push(operand);
+    }
+    Ok(statement)
+}
+
+pub fn parse_condition(cc: &str) -> Option<u8> {
+    let code = match cc.to_ascii_uppercase().as_str() {
+        "" | "UC" => 0b0000,
+        "P" => 0b0001,
+        "LS" => 0b0010,
+        "HI" => 0b0011,
+        "LT" => 0b0100,
+        "GE" => 0b0101,
+        "LE" => 0b0110,
+        "GT" => 0b0111,
+        "B" | "C" | "LO" => 0b1000,
+        "HS" | "NB" | "NC" => 0b1001,
+        "EQ" | "Z" => 0b1010,
+        "NE" | "NZ" => 0b1011,
+        "V" => 0b1100,
+        "NV" => 0b1101,
+        "N" => 0b1110,
+        "NN" => 0b1111,
+        _ => return None,
+    };
+    Some(code)
+}
+
+impl<'a> Context<'a> {
+    pub fn eval(&self, value: &Expr, span: &Range<usize>) -> Result<i64, ParseError> {
+        let here = self.pc.0 as i64;
+        let symbol = |name: &str| match self.symbols.get(name) {
+            Some(n) => Ok(*n),
+            None => match display_hex(name) {
+                Some(n) => Ok(n),
+                None if self.forward => Ok(here),
+                None => Err(format!("undefined symbol '{}'", name)),
+            },
+        };
+        value
+            .eval(here, &symbol)
+            .map_err(|message| ParseError::new(message, span.clone()))
+    }
+
+    // `what` names the operand in the error, e.g. "K" or "IW"
+    pub(crate) fn ranged(
+        &self,
+        operand: &Operand,
+        what: &str,
+        min: i64,
+        max: i64,
+    ) -> Result<i64, ParseError> {
+        let value = match &operand.kind {
+            OperandKind::Immediate(value) | OperandKind::Absolute(value) => {
+                self.eval(value, &operand.span)?
+            }
+            _ => {
+                return Err(ParseError::new(
+                    "expected an immediate value",
+                    operand.span.clone(),
+                ))
+            }
+        };
+        if value < min || value > max {
+            return Err(ParseError::out_of_range(
+                format!("{} {} out of range {}..={}", what, value, min, max),
+                operand.span.clone(),
+            ));
+        }
+        Ok(value)
+    }
+
+    fn long(&self, operand: &Operand) -> Result<u32, ParseError> {
+        Ok(self.ranged(operand, "value", i32::MIN as i64, u32::MAX as i64)? as u32)
+    }
+
+    fn flag(&self, operand: Option<&Operand>) -> Result<bool, ParseError> {
+        match operand {
+            Some(operand) => Ok(self.ranged(operand, "flag", 0, 1)? == 1),
+            None => Ok(false),
+        }
+    }
+
+    fn offset(&self, value: &Expr, span: &Range<usize>) -> Result<Offset, ParseError> {
+        let offset = self.eval(value, span)?;
+        if !(i16::MIN as i64..=u16::MAX as i64).contains(&offset) {
+            return Err(ParseError::out_of_range(
+                format!("offset {} does not fit in 16 bits", offset),
+                span.clone(),
+            ));
+        }
+        Ok(Offset(offset as u16))
+    }
+
+    // word distance from the instruction after this one (which is `size`
+    // words long) to the target address operand
+    fn displacement(&self, operand: &Operand, size: u32) -> Result<i64, ParseError> {
+        let target = self.long(operand)?;
+        if target % 16 != 0 {
+            return Err(ParseError::new(
+                format!("branch target {:X}h is not word aligned", target),
+                operand.span.clone(),
+            ));
+        }
+        // the address space wraps, so work modulo 2^28 words
+        let words = (target / 16).wrapping_sub(self.pc.0 / 16 + size) & 0x0fff_ffff;
+        Ok(((words << 4) as i32 >> 4) as i64)
+    }
+}
+
+// all the registers of an instruction have to come from the same file
+fn common_file(regs: &[(&Register, &Range<usize>)]) -> Result<RegFile, ParseError> {
+    let mut file = None;
+    for (reg, span) in regs {
+        match (file, reg.file) {
+            (Some(a), Some(b)) if a != b => {
+                return Err(ParseError::new(
+                    "registers must be in the same file",
+                    (*span).clone(),
+                ))
+            }
+            (None, Some(b)) => file = Some(b),
+            _ => {}
+        }
+    }
+    Ok(file.unwrap_or(RegFile::A))
+}
+
+fn fits_iw(value: u32) -> bool {
+    (i16::MIN as i32..=i16::MAX as i32).contains(&(value as i32))
+}
+
+pub fn build_instruction(statement: &Statement, ctx: &Context) -> Result<Instruction, ParseError> {
+    use OperandKind::*;
+
+    let mnemonic = match &statement.mnemonic {
+        Some(mnemonic) => mnemonic.to_ascii_uppercase(),
+        None => return Err(ParseError::new("expected an instruction", 0..0)),
+    };
+    let span = statement.mnemonic_span.clone();
+    let ops = &statement.operands;
+    let kinds: Vec<&OperandKind> = ops.iter().map(|op| &op.kind).collect();
+    let bad_operands =
+        || ParseError::new(format!("invalid operands for {}", mnemonic), span.clone());
+    let keyword = |i: usize| match kinds.get(i) {
+        Some(Immediate(Expr::Symbol(sym))) => sym.to_ascii_uppercase(),
+        _ => std::string::String::new(),
+    };
+    // register pairs, in operand order
+    let pair = |a: &self::Register, b: &self::Register| -> Result<(u8, u8), ParseError> {
+        let file = common_file(&[(a, &ops[0].span), (b, &ops[1].span)])?;
+        Ok((a.code(file), b.code(file)))
+    };
+    let single = |a: &self::Register| a.code(RegFile::A);
+
+    if let Some(cc) = mnemonic.strip_prefix("JR") {
+        let condition = parse_condition(cc).ok_or_else(bad_operands)?;
+        // an L after the target forces the long form
+        let long = match (ops.len(), keyword(1).as_str()) {
+            (1, _) => ctx.long,
+            (2, "L") => true,
+            _ => return Err(bad_operands()),
+        };
+        // offsets of 0 and -128 are how the long JR and JA are encoded
+        let short = ctx.displacement(&ops[0], 1)?;
+        if !long && short != 0 && (-127..=127).contains(&short) {
+            return Ok(Instruction::Jrs(
+                Condition(condition),
+                Offset8(short as i8 as u8),
+                PC(ctx.pc),
+            ));
+        }
+        let long = ctx.displacement(&ops[0], 2)?;
+        if !(i16::MIN as i64..=i16::MAX as i64).contains(&long) {
+            return Err(ParseError::out_of_range(
+                "branch target out of range",
+                ops[0].span.clone(),
+            ));
+        }
+        return Ok(Instruction::Jr(
+            Condition(condition),
+            Offset(long as i16 as u16),
+            PC(ctx.pc),
+        ));
+    }
+    if let Some(cc) = mnemonic.strip_prefix("JA") {
+        let condition = parse_condition(cc).ok_or_else(bad_operands)?;
+        if ops.len() != 1 {
+            return Err(bad_operands());
+        }
+        return Ok(Instruction::Ja(
+            Condition(condition),
+            Address(ctx.long(&ops[0])?),
+        ));
+    }
+
+    // MOVE and MOVB both take an optional trailing field select
+    let (field, moves) = match (mnemonic.as_str(), kinds.as_slice()) {
+        ("MOVE", [_, _, Immediate(_)]) => (F(ctx.flag(ops.get(2))?), &kinds[..2]),
+        _ => (F(false), kinds.as_slice()),
+    };
+
+    let inst = match (mnemonic.as_str(), kinds.as_slice()) {
+        (
+            "ADD" | "ADDC" | "ADDXY" | "AND" | "ANDN" | "BTST" | "CMP" | "CMPXY" | "CPW" | "CVXYL"
+            | "DIVS" | "DIVU" | "DRAV" | "LMO" | "MODS" | "MODU" | "MOVX" | "MOVY" | "MPYS"
+            | "MPYU" | "OR" | "RL" | "SLA" | "SLL" | "SRA" | "SRL" | "SUB" | "SUBB" | "SUBXY"
+            | "XOR",
+            [Register(a), Register(b)],
+        ) => {
+            let (rs, rd) = pair(a, b)?;
+            let (rs, rd) = (Rs(rs), Rd(rd));
+            match mnemonic.as_str() {
+                "ADD" => Instruction::Add(rs, rd),
+                "ADDC" => Instruction::Addc(rs, rd),
+                "ADDXY" => Instruction::Addxy(rs, rd),
+                "AND" => Instruction::And(rs, rd),
+                "ANDN" => Instruction::Andn(rs, rd),
+                "BTST" => Instruction::Btst(rs, rd),
+                "CMP" => Instruction::Cmp(rs, rd),
+                "CMPXY" => Instruction::Cmpxy(rs, rd),
+                "CPW" => Instruction::Cpw(rs, rd),
+                "CVXYL" => Instruction::Cvxyl(rs, rd),
+                "DIVS" => Instruction::Divs(rs, rd),
+                "DIVU" => Instruction::Divu(rs, rd),
+                "DRAV" => Instruction::Drav(rs, rd),
+                "LMO" => Instruction::Lmo(rs, rd),
+                "MODS" => Instruction::Mods(rs, rd),
+                "MODU" => Instruction::Modu(rs, rd),
+                "MOVX" => Instruction::Movx(rs, rd),
+                "MOVY" => Instruction::Movy(rs, rd),
+                "MPYS" => Instruction::Mpys(rs, rd),
+                "MPYU" => Instruction::Mpyu(rs, rd),
+                "OR" => Instruction::Or(rs, rd),
+                "RL" => Instruction::Rl(rs, rd),
+                "SLA" => Instruction::Sla(rs, rd),
+                "SLL" => Instruction::Sll(rs, rd),
+                "SRA" => Instruction::Sra(rs, rd),
+                "SRL" => Instruction::Srl(rs, rd),
+                "SUB" => Instruction::Sub(rs, rd),
+                "SUBB" => Instruction::Subb(rs, rd),
+                "SUBXY" => Instruction::Subxy(rs, rd),
+                _ => Instruction::Xor(rs, rd),
+            }
+        }
+        (
+            "ABS" | "CLR" | "DEC" | "GETPC" | "GETST" | "INC" | "NEG" | "NEGB" | "NOT" | "REV",
+            [Register(a)],
+        ) => {
+            let rd = Rd(single(a));
+            match mnemonic.as_str() {
+                "ABS" => Instruction::Abs(rd),
+                "CLR" => Instruction::Clr(rd),
+                "DEC" => Instruction::Dec(rd),
+                "GETPC" => Instruction::Getpc(rd),
+                "GETST" => Instruction::Getst(rd),
+                "INC" => Instruction::Inc(rd),
+                "NEG" => Instruction::Neg(rd),
+                "NEGB" => Instruction::Negb(rd),
+                "NOT" => Instruction::Not(rd),
+                _ => Instruction::Rev(rd),
+            }
+        }
+        ("CALL" | "JUMP" | "PUTST", [Register(a)]) => {
+            let rs = Rs(single(a));
+            match mnemonic.as_str() {
+                "CALL" => Instruction::Call(rs),
+                "JUMP" => Instruction::Jump(rs),
+                _ => Instruction::Putst(rs),
+            }
+        }
+        // unlike EXGF there's no F bit, the opcode has the whole top byte
+        ("EXGPC", [Register(a)]) => Instruction::Exgpc(Rd(single(a)), F(false)),
+        ("SEXT" | "ZEXT" | "EXGF", [Register(a)] | [Register(a), Immediate(_)]) => {
+            let rd = Rd(single(a));
+            let f = F(ctx.flag(ops.get(1))?);
+            match mnemonic.as_str() {
+                "SEXT" => Instruction::Sext(rd, f),
+                "ZEXT" => Instruction::Zext(rd, f),
+                _ => Instruction::Exgf(rd, f),
+            }
+        }
+        ("CLRC" | "DINT" | "EINT" | "EMU" | "NOP" | "POPST" | "PUSHST" | "RETI" | "SETC", []) => {
+            match mnemonic.as_str() {
+                "CLRC" => Instruction::Clrc,
+                "DINT" => Instruction::Dint,
+                "EINT" => Instruction::Eint,
+                "EMU" => Instruction::Emu,
+                "NOP" => Instruction::Nop,
+                "POPST" => Instruction::Popst,
+                "PUSHST" => Instruction::Pushst,
+                "RETI" => Instruction::Reti,
+                _ => Instruction::Setc,
+            }
+        }
+        ("RETS", []) => Instruction::Rets(N(0)),
+        ("RETS", [Immediate(_)]) => Instruction::Rets(N(ctx.ranged(&ops[0], "N", 0, 31)? as u8)),
+        ("TRAP", [Immediate(_)]) => Instruction::Trap(N(ctx.ranged(&ops[0], "N", 0, 31)? as u8)),
+        ("LINE", []) => Instruction::Line(Z(false)),
+        ("LINE", [Immediate(_)]) => Instruction::Line(Z(ctx.flag(ops.first())?)),
+        ("SETF", [Immediate(_), Immediate(_)] | [Immediate(_), Immediate(_), Immediate(_)]) => {
+            let fs = ctx.ranged(&ops[0], "FS", 1, 32)? as u8;
+            Instruction::Setf(
+                FS(fs & 0x1f),
+                FE(ctx.flag(ops.get(1))?),
+                F(ctx.flag(ops.get(2))?),
+            )
+        }
+        ("FILL", [Immediate(_)]) => match keyword(0).as_str() {
+            "L" => Instruction::Filll,
+            "XY" => Instruction::Fillxy,
+            _ => return Err(bad_operands()),
+        },
+        ("PIXBLT", [Immediate(_), Immediate(_)]) => {
+            match (keyword(0).as_str(), keyword(1).as_str()) {
+                ("B", "L") => Instruction::Pixbltbl,
+                ("B", "XY") => Instruction::Pixbltbxy,
+                ("L", "L") => Instruction::Pixbltll,
+                ("L", "XY") => Instruction::Pixbltlxy,
+                ("XY", "L") => Instruction::Pixbltxyl,
+                ("XY", "XY") => Instruction::Pixbltxyxy,
+                _ => return Err(bad_operands()),
+            }
+        }
+        ("ADDK" | "SUBK" | "MOVK", [Immediate(_), Register(a)]) => {
+            // K = 32 is encoded as 0
+            let k = K(ctx.ranged(&ops[0], "K", 1, 32)? as u8 & 0x1f);
+            let rd = Rd(single(a));
+            match mnemonic.as_str() {
+                "ADDK" => Instruction::Addk(k, rd),
+                "SUBK" => Instruction::Subk(k, rd),
+                _ => Instruction::Movk(k, rd),
+            }
+        }
+        ("BTST", [Immediate(_), Register(a)]) => {
+            // stored as one's complement
+            let k = ctx.ranged(&ops[0], "K", 0, 31)? as u8;
+            Instruction::Btstk(K(!k & 0x1f), Rd(single(a)))
+        }
+        (
+            "SLA" | "SLAK" | "SLL" | "SLLK" | "SRA" | "SRAK" | "SRL" | "SRLK" | "RL" | "RLK",
+            [Immediate(_), Register(a)],
+        ) => {
+            let k = ctx.ranged(&ops[0], "K", 0, 31)? as u8;
+            let rd = Rd(single(a));
+            // right shifts store the two's complement of the shift amount
+            let negated = K(0u8.wrapping_sub(k) & 0x1f);
+            match mnemonic.as_str() {
+                "SLA" | "SLAK" => Instruction::Slak(K(k), rd),
+                "SLL" | "SLLK" => Instruction::Sllk(K(k), rd),
+                "SRA" | "SRAK" => Instruction::Srak(negated, rd),
+                "SRL" | "SRLK" => Instruction::Srlk(negated, rd),
+                _ => Instruction::Rlk(K(k), rd),
+            }
+        }
+        (
+            "MOVI" | "ADDI" | "CMPI" | "SUBI",
+            [Immediate(_), Register(_)] | [Immediate(_), Register(_), Immediate(_)],
+        ) => {
+            let value = ctx.long(&ops[0])?;
+            let rd = match kinds[1] {
+                Register(a) => Rd(single(a)),
+                _ => unreachable!(),
+            };
+            let long = match keyword(2).as_str() {
+                "" if ops.len() == 2 => ctx.long || !fits_iw(value),
+                "W" => {
+                    if !fits_iw(value) && value > u16::MAX as u32 {
+                        return Err(ParseError::out_of_range(
+                            format!(
+                                "IW {} out of range {}..={}",
+                                value as i32,
+                                i16::MIN,
+                                u16::MAX
+                            ),
+                            ops[0].span.clone(),
+                        ));
+                    }
+                    false
+                }
+                "L" => true,
+                _ => return Err(bad_operands()),
+            };
+            // CMPI and SUBI store the one's complement of the immediate
+            match (mnemonic.as_str(), long) {
+                ("MOVI", false) => Instruction::Moviw(IW(value as u16), rd),
+                ("MOVI", true) => Instruction::Movil(IL(value), rd),
+                ("ADDI", false) => Instruction::Addiw(IW(value as u16), rd),
+                ("ADDI", true) => Instruction::Addil(IL(value), rd),
+                ("CMPI", false) => Instruction::Cmpiw(IW(!value as u16), rd),
+                ("CMPI", true) => Instruction::Cmpil(IL(!value), rd),
+                ("SUBI", false) => Instruction::Subiw(IW(!value as u16), rd),
+                _ => Instruction::Subil(IL(!value), rd),
+            }
+        }
+        ("ANDI" | "ANDNI" | "ORI" | "XORI", [Immediate(_), Register(a)]) => {
+            let value = ctx.long(&ops[0])?;
+            let rd = Rd(single(a));
+            match mnemonic.as_str() {
+                // ANDI is ANDN with the immediate complemented
+                "ANDI" => Instruction::Andi(IL(!value), rd),
+                "ANDNI" => Instruction::Andi(IL(value), rd),
+                "ORI" => Instruction::Ori(IL(value), rd),
+                _ => Instruction::Xori(IL(value), rd),
+            }
+        }
+        ("MMTM" | "MMFM", [Register(_), ..]) => {
+            let mut regs = vec![];
+            for op in ops {
+                match &op.kind {
+                    Register(reg) => regs.push((reg, &op.span)),
+                    _ => return Err(ParseError::new("expected a register", op.span.clone())),
+                }
+            }
+            let file = common_file(&regs)?;
+            let mut list = 0u16;
+            for (reg, _) in &regs[1..] {
+                list |= 1 << reg.number;
+            }
+            let rp = regs[0].0.code(file);
+            if mnemonic == "MMTM" {
+                // MMTM's mask has R0 in the top bit
+                Instruction::Mmtm(Rd(rp), RegList(list.reverse_bits()))
+            } else {
+                Instruction::Mmfm(Rs(rp), RegList(list))
+            }
+        }
+        ("DSJ" | "DSJEQ" | "DSJNE", [Register(a), Immediate(_)]) => {
+            let rd = Rd(single(a));
+            let disp = ctx.displacement(&ops[1], 2)?;
+            if !(i16::MIN as i64..=i16::MAX as i64).contains(&disp) {
+                return Err(ParseError::out_of_range(
+                    "branch target out of range",
+                    ops[1].span.clone(),
+                ));
+            }
+            let offset = Offset(disp as i16 as u16);
+            match mnemonic.as_str() {
+                "DSJ" => Instruction::Dsj(rd, offset, PC(ctx.pc)),
+                "DSJEQ" => Instruction::Dsjeq(rd, offset, PC(ctx.pc)),
+                _ => Instruction::Dsjne(rd, offset, PC(ctx.pc)),
+            }
+        }
+        ("DSJS", [Register(a), Immediate(_)]) => {
+            let disp = ctx.displacement(&ops[1], 1)?;
+            if !(-31..=31).contains(&disp) {
+                return Err(ParseError::out_of_range(
+                    "branch target out of range",
+                    ops[1].span.clone(),
+                ));
+            }
+            Instruction::Dsjs(
+                D(disp < 0),
+                Rd(single(a)),
+                K(disp.unsigned_abs() as u8),
+                PC(ctx.pc),
+            )
+        }
+        ("CALLR", [Immediate(_)]) => {
+            let disp = ctx.displacement(&ops[0], 2)?;
+            if !(i16::MIN as i64..=i16::MAX as i64).contains(&disp) {
+                return Err(ParseError::out_of_range(
+                    "branch target out of range",
+                    ops[0].span.clone(),
+                ));
+            }
+            Instruction::Callr(Offset(disp as i16 as u16), PC(ctx.pc))
+        }
+        ("CALLA", [Immediate(_)] | [Absolute(_)]) => {
+            Instruction::Calla(Address(ctx.long(&ops[0])?))
+        }
+        ("DW", [Immediate(_)]) => {
+            Instruction::Dw(IW(
+                ctx.ranged(&ops[0], "word", i16::MIN as i64, u16::MAX as i64)? as u16,
+            ))
+        }
+        ("PIXT", [Register(a), Indirect(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::PixtRegToIndirect(Rs(rs), Rd(rd))
+        }
+        ("PIXT", [Register(a), IndirectXY(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::PixtRegToIndirectxy(Rs(rs), Rd(rd))
+        }
+        ("PIXT", [Indirect(a), Register(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::PixtIndirectToReg(Rs(rs), Rd(rd))
+        }
+        ("PIXT", [Indirect(a), Indirect(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::PixtIndirectToIndirect(Rs(rs), Rd(rd))
+        }
+        ("PIXT", [IndirectXY(a), Register(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::PixtIndirectxyToReg(Rs(rs), Rd(rd))
+        }
+        ("PIXT", [IndirectXY(a), IndirectXY(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::PixtIndirectxyToIndirectxy(Rs(rs), Rd(rd))
+        }
+        ("MOVB", [Register(a), Indirect(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::MovbRegToIndirect(Rs(rs), Rd(rd))
+        }
+        ("MOVB", [Indirect(a), Register(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::MovbIndirectToReg(Rs(rs), Rd(rd))
+        }
+        ("MOVB", [Indirect(a), Indirect(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::MovbIndirectToIndirect(Rs(rs), Rd(rd))
+        }
+        ("MOVB", [Register(a), IndirectOffset(b, off)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::MovbRegToIndirectOffset(Rs(rs), Rd(rd), ctx.offset(off, &ops[1].span)?)
+        }
+        ("MOVB", [IndirectOffset(a, off), Register(b)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::MovbIndirectOffsetToReg(Rs(rs), Rd(rd), ctx.offset(off, &ops[0].span)?)
+        }
+        ("MOVB", [IndirectOffset(a, src), IndirectOffset(b, dst)]) => {
+            let (rs, rd) = pair(a, b)?;
+            Instruction::MovbIndirectOffsetToIndirectOffset(
+                Rs(rs),
+                Rd(rd),
+                ctx.offset(src, &ops[0].span)?,
+                ctx.offset(dst, &ops[1].span)?,
+            )
+        }
+        ("MOVB", [Register(a), Absolute(_)]) => {
+            Instruction::MovbRegToAbsolute(Rs(single(a)), Address(ctx.long(&ops[1])?))
+        }
+        ("MOVB", [Absolute(_), Register(b)]) => {
+            Instruction::MovbAbsoluteToReg(Address(ctx.long(&ops[0])?), Rd(single(b)))
+        }
+        ("MOVB", [Absolute(_), Absolute(_)]) => Instruction::MovbAbsoluteToAbsolute(
+            Address(ctx.long(&ops[0])?),
+            Address(ctx.long(&ops[1])?),
+        ),
+        ("MOVE", [Register(a), Register(b)]) => {
+            // the only instruction that can cross register files, so a bare
+            // SP is just A15 here
+            Instruction::MoveReg(Rs(a.code(RegFile::A)), Rd(b.code(RegFile::A)))
+        }
+        ("MOVE", _) => match moves {
+            [Register(a), Absolute(_)] => Instruction::MoveFieldRegToAbsolute(
+                Rs(single(a)),
+                Address(ctx.long(&ops[1])?),
+                field,
+            ),
+            [Absolute(_), Register(b)] => Instruction::MoveFieldAbsoluteToReg(
+                Address(ctx.long(&ops[0])?),
+                Rd(single(b)),
+                field,
+            ),
+            [Absolute(_), Absolute(_)] => Instruction::MoveFieldAbsoluteToAbsolute(
+                Address(ctx.long(&ops[0])?),
+                Address(ctx.long(&ops[1])?),
+                field,
+            ),
+            [Absolute(_), IndirectPostinc(b)] => Instruction::MoveFieldAbsoluteToIndirectPostinc(
+                Address(ctx.long(&ops[0])?),
+                Rd(single(b)),
+                field,
+            ),
+            [Register(a), Indirect(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldRegToIndirect(Rs(rs), Rd(rd), field)
+            }
+            [Register(a), IndirectPredec(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldRegToIndirectPredec(Rs(rs), Rd(rd), field)
+            }
+            [Register(a), IndirectPostinc(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldRegToIndirectPostinc(Rs(rs), Rd(rd), field)
+            }
+            [Register(a), IndirectOffset(b, off)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldRegToIndirectOffset(
+                    Rs(rs),
+                    Rd(rd),
+                    field,
+                    ctx.offset(off, &ops[1].span)?,
+                )
+            }
+            [Indirect(a), Register(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldIndirectToReg(Rs(rs), Rd(rd), field)
+            }
+            [IndirectPredec(a), Register(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldIndirectPredecToReg(Rs(rs), Rd(rd), field)
+            }
+            [IndirectPostinc(a), Register(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldIndirectPostincToReg(Rs(rs), Rd(rd), field)
+            }
+            [Indirect(a), Indirect(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldIndirectToIndirect(Rs(rs), Rd(rd), field)
+            }
+            [IndirectPredec(a), IndirectPredec(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldIndirectToIndirectPredec(Rs(rs), Rd(rd), field)
+            }
+            [IndirectPostinc(a), IndirectPostinc(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldIndirectToIndirectPostinc(Rs(rs), Rd(rd), field)
+            }
+            [IndirectOffset(a, off), Register(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldIndirectOffsetToReg(
+                    Rs(rs),
+                    Rd(rd),
+                    field,
+                    ctx.offset(off, &ops[0].span)?,
+                )
+            }
+            [IndirectOffset(a, off), IndirectPostinc(b)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldIndirectOffsetToIndirectPostinc(
+                    Rs(rs),
+                    Rd(rd),
+                    field,
+                    ctx.offset(off, &ops[0].span)?,
+                )
+            }
+            [IndirectOffset(a, src), IndirectOffset(b, dst)] => {
+                let (rs, rd) = pair(a, b)?;
+                Instruction::MoveFieldIndirectOffsetToIndirectOffset(
+                    Rs(rs),
+                    Rd(rd),
+                    field,
+                    ctx.offset(src, &ops[0].span)?,
+                    ctx.offset(dst, &ops[1].span)?,
+                )
+            }
+            _ => return Err(bad_operands()),
+        },
+        _ => {
+            if is_mnemonic(&mnemonic) {
+                return Err(bad_operands());
+            }
+            return Err(ParseError::new(
+                format!("unknown instruction '{}'", mnemonic),
+                span,
+            ));
+        }
+    };
+    Ok(inst)
+}
+
+fn is_mnemonic(mnemonic: &str) -> bool {
+    const MNEMONICS: &[&str] = &[
+        "ABS", "ADD", "ADDC", "ADDI", "ADDK", "ADDXY", "AND", "ANDI", "ANDN", "ANDNI", "BTST",
+        "CALL", "CALLA", "CALLR", "CLR", "CLRC", "CMP", "CMPI", "CMPXY", "CPW", "CVXYL", "DEC",
+        "DINT", "DIVS", "DIVU", "DRAV", "DSJ", "DSJEQ", "DSJNE", "DSJS", "DW", "EINT", "EMU",
+        "EXGF", "EXGPC", "FILL", "GETPC", "GETST", "INC", "JUMP", "LINE", "LMO", "MMFM", "MMTM",
+        "MODS", "MODU", "MOVB", "MOVE", "MOVI", "MOVK", "MOVX", "MOVY", "MPYS", "MPYU", "NEG",
+        "NEGB", "NOP", "NOT", "OR", "ORI", "PIXBLT", "PIXT", "POPST", "PUSHST", "PUTST", "RETI",
+        "RETS", "REV", "RL", "RLK", "SETC", "SETF", "SEXT", "SLA", "SLAK", "SLL", "SLLK", "SRA",
+        "SRAK", "SRL", "SRLK", "SUB", "SUBB", "SUBI", "SUBK", "SUBXY", "TRAP", "XOR", "XORI",
+        "ZEXT",
+    ];
+    MNEMONICS.contains(&mnemonic)
+}
+
+// Parses a single line with no symbols defined, pc is the address the
+// instruction will be placed at (only matters for relative branches)
+pub fn parse_instruction(line: &str, pc: BitAddress) -> Result<Instruction, ParseError> {
+    let symbols = HashMap::new();
+    let statement = parse_line(line)?;
+    build_instruction(
+        &statement,
+        &Context {
+            pc,
+            symbols: &symbols,
+            long: false,
+            forward: false,
+        },
+    )
+}
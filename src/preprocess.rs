@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::asm::AsmError;
+use crate::expr::{is_ident_char, is_ident_start, parse_expr};
+use crate::parser::{comment_start, split_line, split_operands, Context, ParseError, RawLine};
+use crate::symbol::BitAddress;
+
+// deep enough for any sane program, shallow enough to catch a macro or
+// .include that ends up pulling in itself
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone)]
+pub(crate) struct SourceLine {
+    pub file: Option<Rc<Path>>,
+    // 1-based
+    pub number: usize,
+    pub text: String,
+    // what a .set/.equ on this line works out to, when it doesn't depend on
+    // any addresses
+    pub value: Option<i64>,
+}
+
+impl SourceLine {
+    pub(crate) fn error(&self, error: ParseError) -> AsmError {
+        AsmError {
+            file: self.file.as_ref().map(|file| file.to_path_buf()),
+            line: self.number,
+            error,
+        }
+    }
+}
+
+pub(crate) fn source_lines(source: &str, file: Option<&Path>) -> Vec<SourceLine> {
+    let file: Option<Rc<Path>> = file.map(Rc::from);
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, text)| SourceLine {
+            file: file.clone(),
+            number: i + 1,
+            text: text.to_string(),
+            value: None,
+        })
+        .collect()
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<SourceLine>,
+}
+
+struct Cond {
+    // whether lines in the current branch get assembled
+    active: bool,
+    // whether any branch so far was picked
+    taken: bool,
+    // whether the whole .if sits in an active block
+    enclosing: bool,
+    line: usize,
+}
+
+enum Flow {
+    Done,
+    Break,
+}
+
+#[derive(Default)]
+struct Preprocessor {
+    // keyed by upper case name, macros are called like mnemonics
+    macros: HashMap<String, Macro>,
+    // .set/.equ values known so far, for .if and .loop
+    constants: HashMap<String, i64>,
+    // bumped for every macro expansion and loop iteration, makes `name?`
+    // labels unique
+    expansions: usize,
+    loops: usize,
+    out: Vec<SourceLine>,
+}
+
+fn directive_of(raw: &RawLine) -> String {
+    raw.mnemonic
+        .as_ref()
+        .map(|(mnemonic, _)| mnemonic.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+// Swaps macro parameters for their arguments, either as whole identifiers or
+// forced with :name: (which also works inside strings), and turns `label?`
+// into a label unique to this expansion
+fn substitute(text: &str, params: &[String], args: &[&str], expansion: usize) -> String {
+    if text.starts_with('*') {
+        return text.to_string();
+    }
+    let code_end = comment_start(text);
+    let code = &text[..code_end];
+    let arg = |name: &str| {
+        params
+            .iter()
+            .position(|param| param == name)
+            .map(|i| args.get(i).copied().unwrap_or(""))
+    };
+    let mut out = String::new();
+    let mut quote = None;
+    let mut pos = 0;
+    while let Some(c) = code[pos..].chars().next() {
+        let rest = &code[pos..];
+        if c == ':' {
+            let name_len = rest[1..]
+                .find(|c| !is_ident_char(c))
+                .unwrap_or(rest.len() - 1);
+            if rest[1 + name_len..].starts_with(':') {
+                if let Some(value) = arg(&rest[1..1 + name_len]) {
+                    out.push_str(value);
+                    pos += name_len + 2;
+                    continue;
+                }
+            }
+        }
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, c) if c.is_ascii_digit() => {
+                // a number like 0FFh, which must not have its tail taken
+                // for an identifier
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                out.push_str(&rest[..len]);
+                pos += len;
+                continue;
+            }
+            (None, c) if is_ident_start(c) => {
+                let len = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+                let name = &rest[..len];
+                out.push_str(arg(name).unwrap_or(name));
+                pos += len;
+                if rest[len..].starts_with('?') {
+                    out.push_str(&format!("${}", expansion));
+                    pos += 1;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        out.push(c);
+        pos += c.len_utf8();
+    }
+    out.push_str(&text[code_end..]);
+    out
+}
+
+impl Preprocessor {
+    fn eval(&self, line: &SourceLine, raw: &RawLine) -> Result<i64, AsmError> {
+        let (text, start) = raw.operands;
+        let trimmed = text.trim_start();
+        let start = start + text.len() - trimmed.len();
+        let text = trimmed.trim_end();
+        let span = start..start + text.len();
+        let expr = parse_expr(text, start).map_err(|error| line.error(error))?;
+        if expr.uses_pc() {
+            return Err(line.error(ParseError::new("$ has no value here", span)));
+        }
+        let ctx = Context {
+            pc: BitAddress(0),
+            symbols: &self.constants,
+            long: false,
+            forward: false,
+        };
+        ctx.eval(&expr, &span).map_err(|error| line.error(error))
+    }
+
+    // Takes the lines up to the `close` matching the `open` on lines[*i - 1],
+    // leaving *i just past it
+    fn block(
+        lines: &[SourceLine],
+        i: &mut usize,
+        open: &str,
+        close: &str,
+    ) -> Result<Vec<SourceLine>, AsmError> {
+        let start = *i - 1;
+        let mut nesting = 0;
+        while *i < lines.len() {
+            let line = &lines[*i];
+            *i += 1;
+            let directive = directive_of(&split_line(&line.text));
+            if directive == open {
+                nesting += 1;
+            } else if directive == close {
+                if nesting == 0 {
+                    return Ok(lines[start + 1..*i - 1].to_vec());
+                }
+                nesting -= 1;
+            }
+        }
+        let raw = split_line(&lines[start].text);
+        let span = raw.mnemonic.map(|(_, span)| span).unwrap_or(0..0);
+        Err(lines[start].error(ParseError::new(format!("{} without {}", open, close), span)))
+    }
+
+    // keeps the label of a line that expands into others
+    fn label(&mut self, line: &SourceLine, raw: &RawLine) {
+        if let Some((label, _)) = &raw.label {
+            self.out.push(SourceLine {
+                text: format!("{}:", label),
+                ..line.clone()
+            });
+        }
+    }
+
+    fn include(
+        &mut self,
+        line: &SourceLine,
+        raw: &RawLine,
+        depth: usize,
+    ) -> Result<Flow, AsmError> {
+        let (text, start) = raw.operands;
+        let name = text.trim();
+        let span = start..start + text.len();
+        let name = name
+            .strip_prefix('"')
+            .and_then(|name| name.strip_suffix('"'))
+            .unwrap_or(name);
+        if name.is_empty() {
+            return Err(line.error(ParseError::new("expected a file name", span)));
+        }
+        // relative to the file doing the including
+        let path = match line.file.as_ref().and_then(|file| file.parent()) {
+            Some(dir) => dir.join(name),
+            None => Path::new(name).to_path_buf(),
+        };
+        let source = fs::read_to_string(&path).map_err(|e| {
+            line.error(ParseError::new(
+                format!("cannot read '{}': {}", path.display(), e),
+                span.clone(),
+            ))
+        })?;
+        self.process(&source_lines(&source, Some(&path)), depth + 1)
+    }
+
+    fn process(&mut self, lines: &[SourceLine], depth: usize) -> Result<Flow, AsmError> {
+        let mut conds: Vec<Cond> = vec![];
+        let mut i = 0;
+        while i < lines.len() {
+            let line = &lines[i];
+            i += 1;
+            let raw = split_line(&line.text);
+            let directive = directive_of(&raw);
+            let span = raw.mnemonic.clone().map(|(_, span)| span).unwrap_or(0..0);
+            let error = |message: &str| line.error(ParseError::new(message, span.clone()));
+            let active = conds.last().is_none_or(|cond| cond.active);
+
+            // conditionals are tracked even inside skipped blocks so that
+            // nested ones pair up
+            match directive.as_str() {
+                ".if" => {
+                    let taken = active && self.eval(line, &raw)? != 0;
+                    conds.push(Cond {
+                        active: taken,
+                        taken,
+                        enclosing: active,
+                        line: i - 1,
+                    });
+                    continue;
+                }
+                ".elseif" => {
+                    let cond = match conds.last() {
+                        Some(cond) => cond,
+                        None => return Err(error(".elseif without .if")),
+                    };
+                    let taken = cond.enclosing && !cond.taken && self.eval(line, &raw)? != 0;
+                    let cond = conds.last_mut().unwrap();
+                    cond.active = taken;
+                    cond.taken |= taken;
+                    continue;
+                }
+                ".else" => {
+                    let cond = match conds.last_mut() {
+                        Some(cond) => cond,
+                        None => return Err(error(".else without .if")),
+                    };
+                    cond.active = cond.enclosing && !cond.taken;
+                    cond.taken = true;
+                    continue;
+                }
+                ".endif" => {
+                    if conds.pop().is_none() {
+                        return Err(error(".endif without .if"));
+                    }
+                    continue;
+                }
+                _ if !active => continue,
+                _ => {}
+            }
+            if depth > MAX_DEPTH {
+                return Err(error("macros, loops or includes nested too deeply"));
+            }
+
+            match directive.as_str() {
+                ".macro" => {
+                    let name = match &raw.label {
+                        Some((name, _)) => name.to_ascii_uppercase(),
+                        None => return Err(error(".macro needs a name")),
+                    };
+                    let (text, start) = raw.operands;
+                    let params = if text.trim().is_empty() {
+                        vec![]
+                    } else {
+                        split_operands(text, start)
+                            .into_iter()
+                            .map(|(param, start)| {
+                                if param.starts_with(is_ident_start)
+                                    && param.chars().all(is_ident_char)
+                                {
+                                    Ok(param.to_string())
+                                } else {
+                                    Err(line.error(ParseError::new(
+                                        format!("invalid macro parameter '{}'", param),
+                                        start..start + param.len(),
+                                    )))
+                                }
+                            })
+                            .collect::<Result<_, _>>()?
+                    };
+                    let body = Self::block(lines, &mut i, ".macro", ".endm")?;
+                    self.macros.insert(name, Macro { params, body });
+                }
+                ".loop" => {
+                    let count = if raw.operands.0.trim().is_empty() {
+                        // TI's default, for loops that end with .break
+                        1024
+                    } else {
+                        self.eval(line, &raw)?
+                    };
+                    let body = Self::block(lines, &mut i, ".loop", ".endloop")?;
+                    self.label(line, &raw);
+                    self.loops += 1;
+                    for _ in 0..count {
+                        self.expansions += 1;
+                        let body: Vec<_> = body
+                            .iter()
+                            .map(|body_line| SourceLine {
+                                text: substitute(&body_line.text, &[], &[], self.expansions),
+                                ..body_line.clone()
+                            })
+                            .collect();
+                        if let Flow::Break = self.process(&body, depth + 1)? {
+                            break;
+                        }
+                    }
+                    self.loops -= 1;
+                }
+                ".break" => {
+                    if self.loops == 0 {
+                        return Err(error(".break outside of .loop"));
+                    }
+                    if raw.operands.0.trim().is_empty() || self.eval(line, &raw)? != 0 {
+                        return Ok(Flow::Break);
+                    }
+                }
+                ".endm" => return Err(error(".endm without .macro")),
+                ".endloop" => return Err(error(".endloop without .loop")),
+                ".include" | ".copy" => {
+                    self.label(line, &raw);
+                    if let Flow::Break = self.include(line, &raw, depth)? {
+                        return Ok(Flow::Break);
+                    }
+                }
+                ".set" | ".equ" => {
+                    // values that depend on addresses are left to the
+                    // assembler, and mustn't leave an older value behind
+                    let value = self.eval(line, &raw).ok();
+                    if let Some((name, _)) = &raw.label {
+                        match value {
+                            Some(value) => self.constants.insert(name.to_string(), value),
+                            None => self.constants.remove(*name),
+                        };
+                    }
+                    self.out.push(SourceLine {
+                        value,
+                        ..line.clone()
+                    });
+                }
+                _ if self.macros.contains_key(&directive.to_ascii_uppercase()) => {
+                    let name = directive.to_ascii_uppercase();
+                    let (text, start) = raw.operands;
+                    let args: Vec<&str> = if text.trim().is_empty() {
+                        vec![]
+                    } else {
+                        split_operands(text, start)
+                            .into_iter()
+                            .map(|(arg, _)| arg)
+                            .collect()
+                    };
+                    self.expansions += 1;
+                    let expansion = self.expansions;
+                    let m = &self.macros[&name];
+                    if args.len() > m.params.len() {
+                        return Err(error(&format!(
+                            "too many arguments for macro '{}', expected at most {}",
+                            raw.mnemonic.as_ref().map_or("", |(name, _)| name),
+                            m.params.len()
+                        )));
+                    }
+                    // the expansion reports errors at the line that called it
+                    let body: Vec<_> = m
+                        .body
+                        .iter()
+                        .map(|body_line| SourceLine {
+                            text: substitute(&body_line.text, &m.params, &args, expansion),
+                            ..line.clone()
+                        })
+                        .collect();
+                    self.label(line, &raw);
+                    if let Flow::Break = self.process(&body, depth + 1)? {
+                        return Ok(Flow::Break);
+                    }
+                }
+                _ => self.out.push(line.clone()),
+            }
+        }
+        if let Some(cond) = conds.last() {
+            let line = &lines[cond.line];
+            let span = split_line(&line.text)
+                .mnemonic
+                .map(|(_, span)| span)
+                .unwrap_or(0..0);
+            return Err(line.error(ParseError::new(".if without .endif", span)));
+        }
+        Ok(Flow::Done)
+    }
+}
+
+// Expands macros, loops, conditionals and includes, leaving only the lines
+// the assembler itself understands
+pub(crate) fn preprocess(source: &str, file: Option<&Path>) -> Result<Vec<SourceLine>, AsmError> {
+    let mut preprocessor = Preprocessor::default();
+    preprocessor.process(&source_lines(source, file), 0)?;
+    Ok(preprocessor.out)
+}
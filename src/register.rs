@@ -0,0 +1,113 @@
+use core::fmt::{self, Formatter};
+
+// A register as something an instruction reads or writes. SP is the same
+// register whichever file an instruction names it in. The status register is
+// split into the parts instructions actually care about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Reg {
+    A(u8),
+    B(u8),
+    Sp,
+    N,
+    C,
+    Z,
+    V,
+    Pc,
+    // field sizes and extension modes as set by SETF
+    Fs0,
+    Fe0,
+    Fs1,
+    Fe1,
+}
+
+pub const FLAGS: [Reg; 4] = [Reg::N, Reg::C, Reg::Z, Reg::V];
+
+// Everything in ST that GETST, PUTST and the like move around
+pub const STATUS: [Reg; 8] = [
+    Reg::N,
+    Reg::C,
+    Reg::Z,
+    Reg::V,
+    Reg::Fs0,
+    Reg::Fe0,
+    Reg::Fs1,
+    Reg::Fe1,
+];
+
+// What the graphics instructions use each B register for
+const GRAPHICS_NAMES: [&str; 15] = [
+    "SADDR", "SPTCH", "DADDR", "DPTCH", "OFFSET", "WSTART", "WEND", "DYDX", "COLOR0", "COLOR1",
+    "COUNT", "INC1", "INC2", "PATTRN", "TEMP",
+];
+
+impl Reg {
+    // From the 5 bit register numbers in Rs/Rd, 0-15 for the A file and
+    // 16-31 for the B file
+    pub fn from_number(number: u8) -> Self {
+        match number {
+            15 | 31 => Reg::Sp,
+            0..=14 => Reg::A(number),
+            _ => Reg::B(number - 16),
+        }
+    }
+
+    // FS0 or FS1, for an instruction with an F bit
+    pub fn field_size(f: bool) -> Self {
+        if f {
+            Reg::Fs1
+        } else {
+            Reg::Fs0
+        }
+    }
+
+    pub fn field_extension(f: bool) -> Self {
+        if f {
+            Reg::Fe1
+        } else {
+            Reg::Fe0
+        }
+    }
+
+    // The status bits a jump condition tests, see parser::parse_condition()
+    // for the codes
+    pub fn condition_flags(condition: u8) -> &'static [Reg] {
+        match condition {
+            0 => &[],
+            1 => &[Reg::N, Reg::Z],
+            2 | 3 => &[Reg::C, Reg::Z],
+            4 | 5 => &[Reg::N, Reg::V],
+            6 | 7 => &[Reg::N, Reg::Z, Reg::V],
+            8 | 9 => &[Reg::C],
+            10 | 11 => &[Reg::Z],
+            12 | 13 => &[Reg::V],
+            _ => &[Reg::N],
+        }
+    }
+
+    // SADDR for B0 and so on, None outside the B file
+    pub fn graphics_name(self) -> Option<&'static str> {
+        match self {
+            Reg::B(n) => GRAPHICS_NAMES.get(n as usize).copied(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Reg::A(n) => write!(fmt, "A{}", n),
+            Reg::B(n) => write!(fmt, "B{}", n),
+            Reg::Sp => write!(fmt, "SP"),
+            Reg::N => write!(fmt, "N"),
+            Reg::C => write!(fmt, "C"),
+            Reg::Z => write!(fmt, "Z"),
+            Reg::V => write!(fmt, "V"),
+            Reg::Pc => write!(fmt, "PC"),
+            Reg::Fs0 => write!(fmt, "FS0"),
+            Reg::Fe0 => write!(fmt, "FE0"),
+            Reg::Fs1 => write!(fmt, "FS1"),
+            Reg::Fe1 => write!(fmt, "FE1"),
+        }
+    }
+}
@@ -1,10 +1,15 @@
 use core::fmt::{self, Formatter};
+use core::str::FromStr;
+
+use crate::expr::parse_number;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Rs(pub u8);
 impl fmt::Display for Rs {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        if self.0 == 15 || self.0 == 31 {
+        // SP is register 15 of both files, only the A file one gets the name
+        // so the two encodings stay apart
+        if self.0 == 15 {
             write!(fmt, "SP")
         } else if self.0 <= 14 {
             write!(fmt, "A{}", self.0)
@@ -18,7 +23,9 @@ impl fmt::Display for Rs {
 pub struct Rd(pub u8);
 impl fmt::Display for Rd {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        if self.0 == 15 || self.0 == 31 {
+        // SP is register 15 of both files, only the A file one gets the name
+        // so the two encodings stay apart
+        if self.0 == 15 {
             write!(fmt, "SP")
         } else if self.0 <= 14 {
             write!(fmt, "A{}", self.0)
@@ -113,20 +120,19 @@ pub struct Condition(pub u8);
 impl fmt::Display for Condition {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         let cond_str = match self.0 {
-            // there's some dupes in here I'm not sure how to deal with
-            // need a better understanding of when it's one or the other
+            // there's some dupes in here, the parser accepts the aliases
             0b0000 => "UC",
-            0b0001 => "LO", // also P
+            0b0001 => "P",
             0b0010 => "LS",
             0b0011 => "HI",
             0b0100 => "LT",
-            0b0101 => "GE", // also Z
+            0b0101 => "GE",
             0b0110 => "LE",
             0b0111 => "GT",
-            0b1000 => "B",
+            0b1000 => "B",  // also C/LO
             0b1001 => "HS", // also NB/NC
-            0b1010 => "EQ",
-            0b1011 => "NE",
+            0b1010 => "EQ", // also Z
+            0b1011 => "NE", // also NZ
             0b1100 => "V",
             0b1101 => "NV",
             0b1110 => "N",
@@ -156,4 +162,52 @@ pub struct RegList(pub u16);
 
 // this is a bit of a hack to support instructions with offsets relative to PC
 #[derive(Debug, Clone, Copy)]
-pub struct PC(pub u32);
+pub struct PC(pub BitAddress);
+
+// The TMS34010 addresses memory in bits, so this is what the listing shows,
+// what branches go to and what --pc takes. Instructions always start on a
+// word, a multiple of 16. Arithmetic wraps like the address space does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BitAddress(pub u32);
+
+impl BitAddress {
+    // the start of the word with index `word`, counting from address 0
+    pub fn from_word(word: usize) -> Self {
+        BitAddress((word as u32).wrapping_mul(16))
+    }
+
+    // index of the word this bit is in
+    pub fn word(self) -> usize {
+        (self.0 / 16) as usize
+    }
+
+    pub fn is_word_aligned(self) -> bool {
+        self.0.is_multiple_of(16)
+    }
+
+    pub fn offset_bits(self, bits: i64) -> Self {
+        BitAddress(self.0.wrapping_add(bits as u32))
+    }
+
+    pub fn offset_words(self, words: i64) -> Self {
+        self.offset_bits(words.wrapping_mul(16))
+    }
+}
+
+impl fmt::Display for BitAddress {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{:X}h", self.0)
+    }
+}
+
+// FFC00000h as shown in listings, 0xFFC00000 or plain decimal
+impl FromStr for BitAddress {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_number(text)
+            .and_then(|n| u32::try_from(n).ok())
+            .map(BitAddress)
+            .ok_or_else(|| format!("'{}' is not a valid address", text))
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::asm::AsmError;
+use crate::error::Error;
+use crate::expr::{is_ident_char, is_ident_start};
+use crate::parser::{parse_register, ParseError};
+use crate::symbol::BitAddress;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Code,
+    // data of no particular size
+    Data,
+    Word,
+    Long,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub address: BitAddress,
+    pub name: String,
+    // None if the file doesn't say
+    pub kind: Option<SymbolKind>,
+}
+
+// One symbol per line as "address name [code|data|word|long]", e.g.
+//
+//     FFC00000h  reset_entry  code
+//     01000000h  player_x     long
+//
+// Blank lines and anything after a ';' or '#' are ignored. The addresses are
+// bit addresses like everywhere else.
+pub fn parse_symbols(text: &str, path: Option<&Path>) -> Result<Vec<Symbol>, Error> {
+    let mut symbols = vec![];
+    let mut names = HashSet::new();
+    for (i, line) in text.lines().enumerate() {
+        let code = &line[..line.find([';', '#']).unwrap_or(line.len())];
+        // each field with its span in the line
+        let fields: Vec<_> = code
+            .split_whitespace()
+            .map(|field| {
+                let start = field.as_ptr() as usize - line.as_ptr() as usize;
+                (field, start..start + field.len())
+            })
+            .collect();
+        let error = |message: String, span| -> Error {
+            AsmError {
+                file: path.map(Path::to_path_buf),
+                line: i + 1,
+                error: ParseError::new(message, span),
+            }
+            .into()
+        };
+        let (address, name, kind) = match fields.as_slice() {
+            [] => continue,
+            [address, name] => (address, name, None),
+            [address, name, kind] => (address, name, Some(kind)),
+            _ => {
+                return Err(error(
+                    "expected an address, a name and optionally code, data, word or long"
+                        .to_string(),
+                    0..line.len(),
+                ))
+            }
+        };
+        let address = address
+            .0
+            .parse::<BitAddress>()
+            .map_err(|e| error(e, address.1.clone()))?;
+        let valid = name.0.starts_with(is_ident_start)
+            && name.0.chars().all(is_ident_char)
+            && parse_register(name.0).is_none();
+        if !valid {
+            return Err(error(
+                format!("'{}' is not a valid name", name.0),
+                name.1.clone(),
+            ));
+        }
+        if !names.insert(name.0) {
+            return Err(error(
+                format!("'{}' is already defined", name.0),
+                name.1.clone(),
+            ));
+        }
+        let kind = match kind {
+            None => None,
+            Some((kind, span)) => match kind.to_ascii_lowercase().as_str() {
+                "code" => Some(SymbolKind::Code),
+                "data" => Some(SymbolKind::Data),
+                "word" => Some(SymbolKind::Word),
+                "long" => Some(SymbolKind::Long),
+                _ => {
+                    return Err(error(
+                        format!("unknown kind '{}', expected code, data, word or long", kind),
+                        span.clone(),
+                    ))
+                }
+            },
+        };
+        symbols.push(Symbol {
+            address,
+            name: name.0.to_string(),
+            kind,
+        });
+    }
+    Ok(symbols)
+}
+
+pub fn load_symbols(path: &Path) -> Result<Vec<Symbol>, Error> {
+    parse_symbols(&fs::read_to_string(path)?, Some(path))
+}
+
+// Labels by address for disasm::disassemble_stage2_labeled(), the first name
+// given for an address wins
+pub fn symbol_labels(symbols: &[Symbol]) -> BTreeMap<BitAddress, String> {
+    let mut labels = BTreeMap::new();
+    for symbol in symbols {
+        labels
+            .entry(symbol.address)
+            .or_insert_with(|| symbol.name.clone());
+    }
+    labels
+}
+
+// Every symbol's kind for disasm::Listing, which lists the word, long and
+// data ones as data rather than decoding them, up to the next symbol
+pub fn symbol_kinds(symbols: &[Symbol]) -> BTreeMap<BitAddress, Option<SymbolKind>> {
+    let mut kinds = BTreeMap::new();
+    for symbol in symbols {
+        kinds.entry(symbol.address).or_insert(symbol.kind);
+    }
+    kinds
+}
@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use crate::symbol::BitAddress;
+
+// The trap vectors fill the top 1024 bits of the address space, TRAP 0 (reset)
+// at the very top and each one after it 32 bits lower
+pub const VECTOR_TABLE: BitAddress = BitAddress(0xffff_fc00);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vector {
+    pub trap: u8,
+    // where the vector itself is stored
+    pub address: BitAddress,
+    // and where it points
+    pub handler: BitAddress,
+}
+
+impl Vector {
+    pub fn name(&self) -> String {
+        trap_name(self.trap)
+    }
+}
+
+pub fn vector_address(trap: u8) -> BitAddress {
+    BitAddress(0xffff_ffe0 - 32 * trap as u32)
+}
+
+// The user's guide names for the traps the hardware uses, the rest are only
+// reachable with TRAP n
+pub fn hardware_trap_name(trap: u8) -> Option<&'static str> {
+    let name = match trap {
+        0 => "RESET",
+        1 => "INT1",
+        2 => "INT2",
+        8 => "NMI",
+        9 => "HI",
+        10 => "DI",
+        11 => "WV",
+        30 => "ILLOP",
+        _ => return None,
+    };
+    Some(name)
+}
+
+pub fn trap_name(trap: u8) -> String {
+    match hardware_trap_name(trap) {
+        Some(name) => name.to_string(),
+        None => format!("TRAP{}", trap),
+    }
+}
+
+// The vector for `trap`, if the buffer reaches that far up. Unprogrammed
+// vectors (FFFFFFFFh in an erased ROM, or 0) and anything else that isn't on
+// a word boundary or doesn't point into the buffer can't be a handler we know
+// anything about, so they're left out.
+pub fn read_vector(words: &[u16], start: BitAddress, trap: u8) -> Option<Vector> {
+    let address = vector_address(trap);
+    let offset = address.0.wrapping_sub(start.0);
+    if !offset.is_multiple_of(16) {
+        return None;
+    }
+    let pos = (offset / 16) as usize;
+    let lsw = *words.get(pos)?;
+    let msw = *words.get(pos + 1)?;
+    let handler = BitAddress(lsw as u32 | (msw as u32) << 16);
+    let mapped = (handler.0.wrapping_sub(start.0) / 16) < words.len() as u32;
+    (handler.0 != 0 && handler.is_word_aligned() && mapped).then_some(Vector {
+        trap,
+        address,
+        handler,
+    })
+}
+
+// Every vector in the buffer, reset first
+pub fn read_vectors(words: &[u16], start: BitAddress) -> Vec<Vector> {
+    (0..32)
+        .filter_map(|trap| read_vector(words, start, trap))
+        .collect()
+}
+
+// Labels for the handlers. Unused traps often share a handler, which is then
+// named after the lowest numbered one.
+pub fn vector_labels(vectors: &[Vector]) -> BTreeMap<BitAddress, String> {
+    let mut labels = BTreeMap::new();
+    for vector in vectors {
+        labels
+            .entry(vector.handler)
+            .or_insert_with(|| vector.name());
+    }
+    labels
+}
@@ -0,0 +1,28 @@
+use tms34010_asm::symbol::BitAddress;
+
+#[test]
+fn parses_the_forms_listings_use() {
+    for text in ["FFC00000h", "0ffc00000h", "0xFFC00000", "4290772992"] {
+        assert_eq!(
+            text.parse::<BitAddress>(),
+            Ok(BitAddress(0xffc0_0000)),
+            "{}",
+            text
+        );
+    }
+    assert!("100000000h".parse::<BitAddress>().is_err());
+    assert!("-16".parse::<BitAddress>().is_err());
+    assert!("start".parse::<BitAddress>().is_err());
+}
+
+#[test]
+fn words_and_wrapping() {
+    let address = BitAddress::from_word(0x0ffc_0001);
+    assert_eq!(address, BitAddress(0xffc0_0010));
+    assert_eq!(address.word(), 0x0ffc_0001);
+    assert!(address.is_word_aligned());
+    assert!(!address.offset_bits(8).is_word_aligned());
+    assert_eq!(address.to_string(), "FFC00010h");
+    assert_eq!(BitAddress(0xffff_fff0).offset_words(2), BitAddress(0x10));
+    assert_eq!(BitAddress(0x10).offset_words(-2), BitAddress(0xffff_fff0));
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use tms34010_asm::callgraph::{function_labels, CallGraph};
+use tms34010_asm::symbol::BitAddress;
+
+use common::decode_program;
+
+// sub1 is called directly, sub2 through a register and tail is jumped to,
+// orphan is only found by its prologue
+const PROGRAM: &str = "
+main:
+    CALLA sub1
+    MOVI sub2, A8
+    CALL A8
+    JRUC tail
+sub1:
+    MMTM SP, A0, A1
+    JRZ done
+    CALLR sub2
+done:
+    MMFM SP, A0, A1
+    RETS
+sub2:
+    RETS
+tail:
+    MMTM SP, A1
+    MMFM SP, A1
+    RETS
+orphan:
+    MMTM SP, A2
+    CALLR sub2
+    MMFM SP, A2
+    RETS
+";
+
+fn names() -> BTreeMap<BitAddress, String> {
+    [
+        (0x100000, "main"),
+        (0x100080, "sub1"),
+        (0x100100, "sub2"),
+        (0x100110, "tail"),
+        (0x100160, "orphan"),
+    ]
+    .into_iter()
+    .map(|(address, name)| (BitAddress(address), name.to_string()))
+    .collect()
+}
+
+#[test]
+fn finds_functions_and_their_extents() {
+    let graph = CallGraph::new(
+        &decode_program(PROGRAM, BitAddress(0x100000)),
+        &[BitAddress(0x100000)],
+        &[],
+    );
+    let functions: Vec<_> = graph
+        .functions
+        .values()
+        .map(|function| {
+            (
+                function.entry.0,
+                function.start.0,
+                function.end.0,
+                function.blocks.len(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        functions,
+        [
+            (0x100000, 0x100000, 0x100080, 3),
+            // the JRZ splits it into three
+            (0x100080, 0x100080, 0x100100, 3),
+            (0x100100, 0x100100, 0x100110, 1),
+            (0x100110, 0x100110, 0x100160, 1),
+            (0x100160, 0x100160, 0x1001d0, 2),
+        ]
+    );
+    assert_eq!(
+        graph.register_calls,
+        BTreeMap::from([(BitAddress(0x100060), BitAddress(0x100100))])
+    );
+    let sub2 = &graph.functions[&BitAddress(0x100100)];
+    let callers: Vec<_> = sub2.callers.iter().map(|caller| caller.0).collect();
+    assert_eq!(callers, [0x100000, 0x100080, 0x100160]);
+
+    let labels = function_labels(&graph);
+    assert_eq!(labels[&BitAddress(0x100160)], "SUB_00100160");
+    assert_eq!(labels.len(), 5);
+}
+
+#[test]
+fn reports_as_text() {
+    let graph = CallGraph::new(
+        &decode_program(PROGRAM, BitAddress(0x100000)),
+        &[BitAddress(0x100000)],
+        &[],
+    );
+    assert_eq!(
+        graph.to_text(&names()),
+        "00100000:\tmain\t100000h-100080h
+\tcalls sub1, sub2, tail
+00100080:\tsub1\t100080h-100100h
+\tcalls sub2
+\tcalled by main
+00100100:\tsub2\t100100h-100110h
+\tcalled by main, sub1, orphan
+00100110:\ttail\t100110h-100160h
+\tcalled by main
+00100160:\torphan\t100160h-1001D0h
+\tcalls sub2
+"
+    );
+}
+
+#[test]
+fn exports_to_dot() {
+    let graph = CallGraph::new(
+        &decode_program(PROGRAM, BitAddress(0x100000)),
+        &[BitAddress(0x100000)],
+        &[],
+    );
+    let dot = graph.to_dot(&names());
+    assert!(dot.starts_with("digraph calls {\n"), "{}", dot);
+    assert!(dot.contains("\t\"00100160\" [label=\"orphan\"];\n"));
+    assert!(dot.contains("\t\"00100000\" -> \"00100110\";\n"));
+    assert!(dot.contains("\t\"00100160\" -> \"00100100\";\n"));
+    assert!(dot.ends_with("}\n"));
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use tms34010_asm::cfg::{Cfg, Edge, EdgeKind};
+use tms34010_asm::symbol::BitAddress;
+use tms34010_asm::vectors::{vector_address, Vector};
+
+use common::decode_program;
+
+// a loop calling a subroutine, with a trap on the way out and a handler
+// that nothing else reaches
+const PROGRAM: &str = "
+start:
+    MOVI 10, A0
+loop:
+    CALLR sub
+    DSJ A0, loop
+    JRZ done
+    TRAP 1
+done:
+    JRUC start
+sub:
+    ADDK 1, A1
+    RETS
+handler:
+    RETI
+    .word 0FFFFh
+";
+
+fn edge(from: u32, to: u32, kind: EdgeKind) -> Edge {
+    Edge {
+        from: BitAddress(from),
+        to: BitAddress(to),
+        kind,
+    }
+}
+
+#[test]
+fn splits_the_code_into_blocks() {
+    let vectors = [Vector {
+        trap: 1,
+        address: vector_address(1),
+        handler: BitAddress(0xb0),
+    }];
+    let cfg = Cfg::new(
+        &decode_program(PROGRAM, BitAddress(0)),
+        &[BitAddress(0)],
+        &vectors,
+    );
+    let blocks: Vec<_> = cfg
+        .blocks
+        .values()
+        .map(|block| (block.start.0, block.instructions.len(), block.end().0))
+        .collect();
+    assert_eq!(
+        blocks,
+        [
+            (0x00, 1, 0x20),
+            (0x20, 1, 0x40),
+            (0x40, 1, 0x60),
+            (0x60, 1, 0x70),
+            (0x70, 1, 0x80),
+            (0x80, 1, 0x90),
+            // the subroutine, and the handler only reached through the trap
+            (0x90, 2, 0xb0),
+            (0xb0, 1, 0xc0),
+        ]
+    );
+    use EdgeKind::*;
+    assert_eq!(
+        cfg.edges,
+        [
+            edge(0x00, 0x20, Fallthrough),
+            edge(0x20, 0x90, Call),
+            edge(0x20, 0x40, Fallthrough),
+            edge(0x40, 0x20, Branch),
+            edge(0x40, 0x60, Fallthrough),
+            edge(0x60, 0x80, Branch),
+            edge(0x60, 0x70, Fallthrough),
+            edge(0x70, 0xb0, Trap),
+            edge(0x70, 0x80, Fallthrough),
+            edge(0x80, 0x00, Branch),
+        ]
+    );
+    assert_eq!(cfg.predecessors(BitAddress(0x20)).count(), 2);
+    assert_eq!(cfg.successors(BitAddress(0x90)).count(), 0);
+}
+
+#[test]
+fn jumping_into_a_block_splits_it() {
+    // the JRNZ goes back to the MOVK, past the first one
+    let inst_vec = decode_program("MOVK 1, A0\nMOVK 2, A1\nJRNZ 10h\nRETS", BitAddress(0));
+    let cfg = Cfg::new(&inst_vec, &[BitAddress(0)], &[]);
+    let starts: Vec<_> = cfg.blocks.keys().map(|start| start.0).collect();
+    assert_eq!(starts, [0x00, 0x10, 0x30]);
+    assert_eq!(cfg.blocks[&BitAddress(0x10)].instructions.len(), 2);
+}
+
+#[test]
+fn exports_to_dot() {
+    let cfg = Cfg::new(
+        &decode_program(PROGRAM, BitAddress(0)),
+        &[BitAddress(0)],
+        &[],
+    );
+    let labels = BTreeMap::from([(BitAddress(0x90), "add_one".to_string())]);
+    let dot = cfg.to_dot(&labels);
+    assert!(dot.starts_with("digraph cfg {\n"), "{}", dot);
+    assert!(dot.ends_with("}\n"));
+    assert!(dot
+        .contains("\t\"00000090\" [label=\"add_one:\\l00000090: INC A1\\l000000A0: RETS\\l\"];\n"));
+    assert!(dot.contains("\t\"00000020\" [label=\"00000020: CALLR add_one\\l\"];\n"));
+    assert!(dot.contains("\t\"00000020\" -> \"00000090\" [style=dashed, label=\"call\"];\n"));
+    assert!(dot.contains("\t\"00000000\" -> \"00000020\";\n"));
+}
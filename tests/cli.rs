@@ -0,0 +1,74 @@
+use std::fs;
+use std::process::Command;
+
+// NOP, RETS with the NOP skipped by --offset, so the buffer is bigger than
+// what's left of the file
+#[test]
+fn offset_does_not_read_past_the_end() {
+    let path = std::env::temp_dir().join(format!("cli-offset-{}.bin", std::process::id()));
+    fs::write(&path, [0x00, 0x03, 0x60, 0x09]).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_tms34010-disasm"))
+        .arg(&path)
+        .args(["--offset", "2"])
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("RETS"), "{}", stdout);
+    assert!(
+        !stdout.contains("NOP") && !stdout.contains("DW"),
+        "{}",
+        stdout
+    );
+}
+
+// Numbers take the same notations as --pc, and bad ones are reported rather
+// than panicking
+#[test]
+fn numbers_are_checked() {
+    let path = std::env::temp_dir().join(format!("cli-numbers-{}.bin", std::process::id()));
+    fs::write(&path, [0x00, 0x03, 0x60, 0x09]).unwrap();
+    let run = |bin: &str, args: &[&str]| Command::new(bin).arg(&path).args(args).output().unwrap();
+    let disasm = env!("CARGO_BIN_EXE_tms34010-disasm");
+    let output = run(disasm, &["--offset", "2h"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("RETS"));
+    for args in [["--offset", "100q"], ["--size", "0x"]] {
+        let output = run(disasm, &args);
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("is not a valid number"), "{}", stderr);
+    }
+
+    let out = path.with_extension("out");
+    let out = out.to_str().unwrap();
+    let asm = env!("CARGO_BIN_EXE_tms34010-as");
+    for (args, message) in [
+        ([out, "--fill", "100h"], "fill '100h' is out of range"),
+        ([out, "--size", "ten"], "size 'ten' is not a valid number"),
+    ] {
+        let output = run(asm, &args);
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(stderr.trim_end(), message);
+    }
+    fs::remove_file(&path).unwrap();
+}
+
+// RETS then words that would look like a function after it on a linear
+// sweep, MMTM SP, A0 and another RETS
+#[test]
+fn call_graph_follows_the_code() {
+    let path = std::env::temp_dir().join(format!("cli-graph-{}.bin", std::process::id()));
+    fs::write(&path, [0x60, 0x09, 0x8f, 0x09, 0x00, 0x80, 0x60, 0x09]).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_tms34010-disasm"))
+        .arg(&path)
+        .args(["--call-graph", "text", "--entry", "0"])
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "00000000:\tSUB_00000000\t0h-10h\n");
+}
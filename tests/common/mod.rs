@@ -0,0 +1,24 @@
+// Helpers shared by the integration tests, not every test uses all of them
+#![allow(dead_code)]
+
+use tms34010_asm::asm::assemble;
+use tms34010_asm::disasm::{disassemble_stage1, DecodedInstruction, Decoder};
+use tms34010_asm::instruction::Instruction;
+use tms34010_asm::symbol::BitAddress;
+
+// little endian, the way the disassembler reads files
+pub fn to_bytes(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+// assembles a single instruction at 1000h and decodes it again
+pub fn decode(source: &str) -> Instruction {
+    let words = assemble(source, BitAddress(0x1000)).unwrap().words;
+    Decoder::decode_one(&words, BitAddress(0x1000)).0
+}
+
+// assembles a program at `start` and runs it through the disassembler
+pub fn decode_program(source: &str, start: BitAddress) -> Vec<DecodedInstruction> {
+    let bytes = assemble(source, start).unwrap().to_bytes();
+    disassemble_stage1(&bytes, start)
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use tms34010_asm::asm::assemble_listing;
+use tms34010_asm::disasm::{disassemble_stage1, disassemble_stage2, Decoder};
+use tms34010_asm::instruction::Instruction;
+use tms34010_asm::symbol::BitAddress;
+use tms34010_asm::Error;
+
+use common::to_bytes;
+
+// NOP, MOVI 12345678h, A0, JR UC back to the NOP, a reserved opcode, RETS
+const PROGRAM: [u16; 7] = [0x0300, 0x09e0, 0x5678, 0x1234, 0xc0fb, 0x0000, 0x0960];
+
+#[test]
+fn decodes_at_any_address() {
+    let (inst, len) = Decoder::decode_one(&PROGRAM[1..], BitAddress(0x1010));
+    assert_eq!(len, 3);
+    assert_eq!(inst.to_string(), "MOVI 12345678h, A0");
+    assert_eq!(*inst.encoding(), PROGRAM[1..4]);
+
+    // the branch target depends on where the instruction is
+    let (inst, len) = Decoder::decode_one(&PROGRAM[4..], BitAddress(0x1040));
+    assert_eq!(len, 1);
+    assert_eq!(inst.to_string(), "JRUC 1000h");
+
+    // starting in the middle of the MOVI just finds whatever is there
+    let (inst, len) = Decoder::decode_one(&PROGRAM[2..], BitAddress(0x1020));
+    assert_eq!(len, 1);
+    assert_eq!(inst.get_mnemonic(), "XOR");
+}
+
+#[test]
+fn bad_words_are_single_dws() {
+    assert!(matches!(
+        Decoder::decode_one(&PROGRAM[5..], BitAddress(0x1050)),
+        (Instruction::Dw(_), 1)
+    ));
+    assert!(matches!(
+        Decoder::try_decode_one(&PROGRAM[5..], BitAddress(0x1050)),
+        Err(Error::InvalidEncoding {
+            address: BitAddress(0x1050),
+            word: 0
+        })
+    ));
+    assert!(matches!(
+        Decoder::try_decode_one(&PROGRAM[1..3], BitAddress(0x1010)),
+        Err(Error::Truncated {
+            address: BitAddress(0x1010)
+        })
+    ));
+    assert!(matches!(
+        Decoder::decode_one(&[], BitAddress(0)),
+        (Instruction::Dw(_), 0)
+    ));
+}
+
+#[test]
+fn iterating_matches_stage1() {
+    let bytes = to_bytes(&PROGRAM);
+    let stage1 = disassemble_stage1(&bytes, BitAddress(0x1000));
+    let decoded: Vec<_> = Decoder::new(&PROGRAM, BitAddress(0x1000)).collect();
+    assert_eq!(decoded.len(), stage1.len());
+    for (a, b) in decoded.iter().zip(&stage1) {
+        assert_eq!((a.pc, a.address, a.words()), (b.pc, b.address, b.words()));
+        assert_eq!(a.instruction.to_string(), b.instruction.to_string());
+    }
+}
+
+#[test]
+fn decoded_instructions_know_their_addresses() {
+    let decoded: Vec<_> = Decoder::new(&PROGRAM, BitAddress(0x1000)).collect();
+    let movi = &decoded[1];
+    assert_eq!(
+        (movi.pc, movi.address, movi.size),
+        (0x101, BitAddress(0x1010), 48)
+    );
+    assert_eq!(movi.words(), [0x09e0, 0x5678, 0x1234]);
+    assert_eq!(movi.next_address(), BitAddress(0x1040));
+    assert_eq!(movi.branch_target(), None);
+    assert!(!movi.is_terminator());
+
+    let jruc = &decoded[2];
+    assert_eq!(jruc.address, BitAddress(0x1040));
+    assert_eq!(jruc.branch_target(), Some(BitAddress(0x1000)));
+    assert!(jruc.is_terminator());
+
+    let rets = &decoded[4];
+    assert_eq!(rets.next_address(), BitAddress(0x1070));
+    assert!(rets.is_terminator());
+}
+
+#[test]
+fn branch_targets_match_the_listing() {
+    // JREQ, DSJ, DSJS backwards and forwards, CALLR, CALLA
+    let words = [
+        0xca05, 0x0d81, 0xfff0, 0x3c61, 0x3841, 0x0d3f, 0x0020, 0x0d5f, 0x0000, 0xffc0,
+    ];
+    for decoded in Decoder::new(&words, BitAddress(0x80000)) {
+        let target = decoded.branch_target().unwrap();
+        assert!(!decoded.is_terminator());
+        let text = decoded.instruction.to_string();
+        assert!(text.ends_with(&target.to_string()), "{}", text);
+    }
+}
+
+#[test]
+fn dont_care_bits_are_ignored_but_kept() {
+    // NOP with bit 0 set, MOVI IW, JRUC back to the start
+    let words = [0x0301, 0x09c0, 0x1234, 0xc0fc];
+    let (inst, len) = Decoder::decode_one(&words, BitAddress(0));
+    assert!(matches!((inst, len), (Instruction::Nop, 1)));
+    assert!(matches!(
+        Decoder::try_decode_one(&words, BitAddress(0)),
+        Err(Error::InvalidEncoding { word: 0x0301, .. })
+    ));
+
+    let decoded: Vec<_> = Decoder::new(&words, BitAddress(0x1000)).collect();
+    assert_eq!(decoded[0].dont_care_bits(), 1);
+    assert_eq!(decoded[1].dont_care_bits(), 0);
+    assert_eq!(decoded[2].branch_target(), Some(BitAddress(0x1000)));
+
+    // the listing shows the words as they are so it reassembles exactly
+    let bytes = to_bytes(&words);
+    let listing = disassemble_stage2(disassemble_stage1(&bytes, BitAddress(0x1000)));
+    let first = listing.lines().next().unwrap();
+    assert!(first.contains(".word 0301h"), "{}", first);
+    assert!(
+        first.ends_with("; NOP with don't-care bits 0001h"),
+        "{}",
+        first
+    );
+    let assembly = assemble_listing(&listing, None).unwrap();
+    assert_eq!(assembly.words, words);
+}
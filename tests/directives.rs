@@ -0,0 +1,80 @@
+use tms34010_asm::asm::assemble;
+use tms34010_asm::symbol::BitAddress;
+
+fn words(source: &str) -> Vec<u16> {
+    match assemble(source, BitAddress(0x1000)) {
+        Ok(assembly) => assembly.words,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+fn error(source: &str) -> String {
+    match assemble(source, BitAddress(0x1000)) {
+        Ok(assembly) => panic!("{:04X?}", assembly.words),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn space_size_from_constant() {
+    let source = "SIZE .set 32\n .space SIZE\n .word 1";
+    assert_eq!(words(source), [0, 0, 1]);
+}
+
+#[test]
+fn field_width_from_constant() {
+    let source = "W .equ 8\n .field 12h, W\n .field 34h, W";
+    assert_eq!(words(source), [0x3412]);
+}
+
+#[test]
+fn space_size_from_labels() {
+    let source = "start: .word 1, 2\nend:\n .space end - start\n .word 3";
+    assert_eq!(words(source), [1, 2, 0, 0, 3]);
+}
+
+#[test]
+fn align_from_constant() {
+    let source = "A .set 40h\n .word 1\n .align A\n .word 2";
+    assert_eq!(words(source), [1, 0, 0, 0, 2]);
+}
+
+#[test]
+fn sizes_cannot_look_ahead() {
+    assert!(error(" .space SIZE\nSIZE .set 16").contains("undefined symbol"));
+    assert!(error(" .space end - start\nstart: .word 1\nend:").contains("undefined symbol"));
+}
+
+#[test]
+fn set_can_be_reassigned() {
+    let source = "N .set 0\n .loop 3\nN .set N + 1\n .word N\n .endloop\n .word N";
+    assert_eq!(words(source), [1, 2, 3, 3]);
+    // in the assembler too, for values that depend on labels
+    let source = "X .set here\nhere: .word X\nX .set there\nthere: .word X";
+    assert_eq!(words(source), [0x1000, 0x1010]);
+}
+
+#[test]
+fn labels_and_equ_cannot_be_reassigned() {
+    assert!(error("N .equ 1\nN .equ 2").contains("'N' is already defined"));
+    assert!(error("N .set 1\nN .equ 2").contains("'N' is already defined"));
+    assert!(error("N .equ 1\nN .set 2").contains("'N' is already defined"));
+    assert!(error("N: .word 1\nN .set 2").contains("'N' is already defined"));
+}
+
+#[test]
+fn conditions_see_the_latest_value() {
+    // a value the preprocessor can't work out mustn't leave the old one
+    // behind for .if
+    let source = "N .set 1\nN .set here\nhere:\n .if N == 1\n .word 1\n .endif";
+    assert!(error(source).contains("undefined symbol"));
+    let source = "N .set 1\nN .set N * 2\n .if N == 2\n .word 2\n .endif";
+    assert_eq!(words(source), [2]);
+}
+
+#[test]
+fn bytes_end_where_they_stop() {
+    let assembly = assemble(" .word 1\n .byte 41h, \"b\", 0FFh", BitAddress(0x1000)).unwrap();
+    assert_eq!(assembly.words, [1, 0x6241, 0xff]);
+    assert_eq!(assembly.to_bytes(), [1, 0, 0x41, 0x62, 0xff]);
+}
@@ -0,0 +1,31 @@
+use tms34010_asm::instruction::Instruction;
+use tms34010_asm::symbol::{Address, BitAddress, Condition, Offset, Rd, Rs, F, IL, PC};
+
+// The operands come out in the order the TI assembler takes them
+#[test]
+fn operands_are_in_source_order() {
+    let movb = Instruction::MovbIndirectOffsetToReg(Rs(1), Rd(2), Offset(0xfffe));
+    assert_eq!(movb.to_string(), "MOVB *A1(-2), A2");
+    let movb = Instruction::MovbIndirectOffsetToIndirectOffset(Rs(1), Rd(2), Offset(4), Offset(8));
+    assert_eq!(movb.to_string(), "MOVB *A1(4), *A2(8)");
+    let move_field = Instruction::MoveFieldRegToIndirect(Rs(1), Rd(2), F(false));
+    assert_eq!(move_field.to_string(), "MOVE A1, *A2, 0");
+    let andi = Instruction::Andi(IL(!0x7f), Rd(3));
+    assert_eq!(andi.to_string(), "ANDI 7Fh, A3");
+}
+
+#[test]
+fn jumps_show_their_target() {
+    // no comma between the condition and the address
+    let ja = Instruction::Ja(Condition(0b1010), Address(0x12340));
+    assert_eq!(ja.to_string(), "JAEQ 12340h");
+    // the offset counts from the end of the two word instruction
+    let jr = Instruction::Jr(Condition(0), Offset(0x200), PC(BitAddress(0x1000)));
+    assert_eq!(jr.to_string(), "JRUC 3020h");
+}
+
+#[test]
+fn condition_0001_is_plus() {
+    assert_eq!(Condition(0b0001).to_string(), "P");
+    assert_eq!(Condition(0b1000).to_string(), "B");
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use tms34010_asm::asm::assemble;
+use tms34010_asm::disasm::{disassemble_stage1, try_disassemble_stage1, try_disassemble_stage2};
+use tms34010_asm::symbol::BitAddress;
+use tms34010_asm::Error;
+
+use common::to_bytes;
+
+#[test]
+fn strict_decoding_matches_lenient_for_code() {
+    // NOP, MOVI 1234h, A0, RETS
+    let bytes = to_bytes(&[0x0300, 0x09c0, 0x1234, 0x0960]);
+    let strict = try_disassemble_stage1(&bytes, BitAddress(0x1000)).unwrap();
+    let lenient = disassemble_stage1(&bytes, BitAddress(0x1000));
+    assert_eq!(strict.len(), lenient.len());
+    for (a, b) in strict.iter().zip(&lenient) {
+        assert_eq!((a.pc, a.words()), (b.pc, b.words()));
+    }
+}
+
+#[test]
+fn strict_decoding_reports_truncation() {
+    // MOVI IL missing its last word
+    let bytes = to_bytes(&[0x0300, 0x09e0, 0x1234]);
+    match try_disassemble_stage1(&bytes, BitAddress(0x1000)) {
+        Err(Error::Truncated { address }) => assert_eq!(address, BitAddress(0x1010)),
+        other => panic!("{:?}", other),
+    }
+    match try_disassemble_stage1(&[0x00, 0x03, 0x00], BitAddress(0)) {
+        Err(Error::Truncated { address }) => assert_eq!(address, BitAddress(0x10)),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn strict_decoding_reports_invalid_encodings() {
+    // a reserved opcode, then NOP with a don't-care bit set
+    for word in [0x0000, 0x0301] {
+        let bytes = to_bytes(&[0x0300, word]);
+        match try_disassemble_stage1(&bytes, BitAddress(0x1000)) {
+            Err(Error::InvalidEncoding { address, word: w }) => {
+                assert_eq!((address, w), (BitAddress(0x1010), word))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
+
+#[test]
+fn stage2_reports_write_errors() {
+    struct Full;
+    impl std::io::Write for Full {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::WriteZero.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let decoded = disassemble_stage1(&[0x00, 0x03], BitAddress(0));
+    assert!(matches!(
+        try_disassemble_stage2(decoded, &mut Full),
+        Err(Error::Io(_))
+    ));
+}
+
+#[test]
+fn assembler_errors_are_classified() {
+    match assemble("NOP\n ADDK 33, A0", BitAddress(0)) {
+        Err(Error::OutOfRange(e)) => {
+            assert_eq!(e.line, 2);
+            assert_eq!(e.error.span, 6..8);
+        }
+        other => panic!("{:?}", other.map(|a| a.words)),
+    }
+    match assemble("ADDK A0", BitAddress(0)) {
+        Err(Error::Parse(e)) => assert_eq!(e.line, 1),
+        other => panic!("{:?}", other.map(|a| a.words)),
+    }
+}
+
+#[test]
+fn exgpc_has_no_field_operand() {
+    assert_eq!(
+        assemble(" EXGPC A1", BitAddress(0)).unwrap().words,
+        [0x0121]
+    );
+    match assemble(" EXGPC A1, 1", BitAddress(0)) {
+        Err(Error::Parse(e)) => assert_eq!(e.line, 1),
+        other => panic!("{:?}", other.map(|a| a.words)),
+    }
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use tms34010_asm::asm::{assemble, assemble_listing};
+use tms34010_asm::disasm::{disassemble_stage2_with, Listing};
+use tms34010_asm::fields::{track_fields, FieldState};
+use tms34010_asm::symbol::BitAddress;
+
+use common::decode_program;
+
+const PROGRAM: &str = "
+    SETF 8, 0, 1
+    MOVE *A0+, A1, 1
+    MOVE A1, *A2, 1
+    MOVE A1, *A2, 0
+    JRZ skip
+    SETF 8, 1, 1
+skip:
+    MOVE *A0, A1, 1
+    SEXT A3, 1
+    CALLR sub
+    MOVE *A0, A1, 1
+    RETS
+sub:
+    ZEXT A3, 1
+    RETS
+";
+
+#[test]
+fn field_moves_are_commented_with_their_size() {
+    let start = BitAddress(0x10000);
+    let inst_vec = decode_program(PROGRAM, start);
+    let entries = BTreeMap::from([(start, FieldState::default())]);
+    let listing = Listing {
+        fields: track_fields(&inst_vec, &entries),
+        ..Listing::default()
+    };
+    let text = disassemble_stage2_with(inst_vec, &listing);
+    let comments: Vec<_> = text
+        .lines()
+        .map(|line| {
+            let code = line.split("  ").next().unwrap().split('\t').nth(1).unwrap();
+            let comment = line.split_once(" ; ").map_or("", |(_, comment)| comment);
+            (code, comment)
+        })
+        .collect();
+    assert_eq!(
+        comments,
+        [
+            ("SETF 8, 0, 1", ""),
+            ("MOVE *A0+, A1, 1", "8-bit zext"),
+            // storing doesn't extend anything
+            ("MOVE A1, *A2, 1", "8-bit"),
+            // nothing known at the entry
+            ("MOVE A1, *A2, 0", ""),
+            ("JREQ 10060h", ""),
+            ("SETF 8, 1, 1", ""),
+            // the two paths only agree on the size
+            ("MOVE *A0, A1, 1", "8-bit"),
+            ("SEXT A3, 1", "8-bit"),
+            ("CALLR 100C0h", ""),
+            // the call might have changed it
+            ("MOVE *A0, A1, 1", ""),
+            ("RETS", ""),
+            // from the caller
+            ("ZEXT A3, 1", "8-bit"),
+            ("RETS", ""),
+        ]
+    );
+
+    // the comments don't get in the assembler's way
+    assert_eq!(
+        assemble_listing(&text, None).unwrap().words,
+        assemble(PROGRAM, start).unwrap().words
+    );
+}
+
+#[test]
+fn traps_start_from_the_reset_state() {
+    let inst_vec = decode_program("MOVE *A0, A1, 0\nMOVE *A0, A1, 1", BitAddress(0));
+    let entries = BTreeMap::from([(BitAddress(0), FieldState::RESET)]);
+    let states = track_fields(&inst_vec, &entries);
+    let described: Vec<_> = inst_vec
+        .iter()
+        .map(|decoded| states[&decoded.address].describe(&decoded.instruction))
+        .collect();
+    assert_eq!(
+        described,
+        [
+            Some("16-bit zext".to_string()),
+            Some("32-bit zext".to_string())
+        ]
+    );
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use tms34010_asm::disasm::disassemble_stage2;
+use tms34010_asm::flow::{disassemble_flow, trace};
+use tms34010_asm::instruction::Instruction;
+use tms34010_asm::symbol::BitAddress;
+
+use common::to_bytes;
+
+// At 1000h: JRNE over a table to the RETS, JRUC over the table as well, a
+// table that looks like MOVI then a JRUC, and a separate routine at 1070h
+const PROGRAM: [u16; 9] = [
+    0xcb05, // JRNE 1060h
+    0xc004, // JRUC 1060h
+    0x09e0, 0x5678, // table
+    0xc000, 0x0000, // JRUC 1060h, L  (unreached)
+    0x0960, // 1060h RETS
+    0x0300, // 1070h NOP
+    0x0960, // 1080h RETS
+];
+
+#[test]
+fn unreached_words_are_data() {
+    let listing = disassemble_stage2(disassemble_flow(
+        &to_bytes(&PROGRAM),
+        BitAddress(0x1000),
+        &[BitAddress(0x1000), BitAddress(0x1070)],
+    ));
+    // without the raw words on the right
+    let lines: Vec<_> = listing
+        .lines()
+        .map(|line| line.split("  ").next().unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "00001000:\tJRNE 1060h",
+            "00001010:\tJRUC 1060h",
+            "00001020:\tDW 09E0h",
+            "00001030:\tDW 5678h",
+            "00001040:\tDW C000h",
+            "00001050:\tDW 0000h",
+            "00001060:\tRETS",
+            "00001070:\tNOP",
+            "00001080:\tRETS",
+        ]
+    );
+}
+
+#[test]
+fn follows_calls_and_stops_at_returns() {
+    // CALLR 1040h, RETS, junk, NOP, RETS
+    let words = [0x0d3f, 0x0002, 0x0960, 0xffff, 0x0300, 0x0960];
+    let code = trace(&words, BitAddress(0x1000), &[BitAddress(0x1000)]);
+    let addresses: Vec<_> = code.keys().map(|address| address.0).collect();
+    assert_eq!(addresses, [0x1000, 0x1020, 0x1040, 0x1050]);
+    assert!(matches!(
+        code[&BitAddress(0x1000)].instruction,
+        Instruction::Callr(..)
+    ));
+}
+
+#[test]
+fn starts_from_the_vectors() {
+    // the last 32 words of the address space: trap 15 through reset, then
+    // a couple of instructions just below them
+    let start = BitAddress(0xfffffc00 - 0x20);
+    let mut words = vec![0x0300, 0x0960];
+    let mut vectors = vec![0xffff; 64];
+    // reset points at the NOP, trap 1 at the RETS
+    vectors[62] = 0xfbe0;
+    vectors[63] = 0xffff;
+    vectors[60] = 0xfbf0;
+    vectors[61] = 0xffff;
+    words.extend(vectors);
+
+    let inst_vec = disassemble_flow(&to_bytes(&words), start, &[]);
+    assert_eq!(inst_vec[0].instruction.get_mnemonic(), "NOP");
+    assert_eq!(inst_vec[1].instruction.get_mnemonic(), "RETS");
+    assert!(inst_vec[2..]
+        .iter()
+        .all(|decoded| decoded.instruction.get_mnemonic() == "DW"));
+}
+
+#[test]
+fn overlapping_paths_keep_the_first() {
+    // MOVI 0C0000000h, A0 then RETS, with an entry in the middle of the MOVI
+    let words = [0x09e0, 0x0000, 0xc000, 0x0960];
+    let code = trace(&words, BitAddress(0), &[BitAddress(0), BitAddress(0x20)]);
+    let addresses: Vec<_> = code.keys().map(|address| address.0).collect();
+    assert_eq!(addresses, [0, 0x30]);
+}
+
+#[test]
+fn dont_care_bits_do_not_stop_the_trace() {
+    // NOP with a don't-care bit set, then RETS
+    let code = trace(&[0x0301, 0x0960], BitAddress(0), &[BitAddress(0)]);
+    assert!(matches!(code[&BitAddress(0)].instruction, Instruction::Nop));
+    assert!(matches!(
+        code[&BitAddress(0x10)].instruction,
+        Instruction::Rets(_)
+    ));
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use tms34010_asm::asm::{assemble, assemble_listing};
+use tms34010_asm::disasm::{disassemble_stage1, disassemble_stage2_with, Listing};
+use tms34010_asm::register::Reg;
+use tms34010_asm::symbol::BitAddress;
+
+use common::decode;
+
+fn b(regs: &[u8]) -> BTreeSet<Reg> {
+    regs.iter().map(|n| Reg::B(*n)).collect()
+}
+
+#[test]
+fn graphics_instructions_report_their_b_registers() {
+    let fill = decode("FILL XY");
+    assert_eq!(fill.implicit_reads(), b(&[2, 3, 4, 5, 6, 7, 9]));
+    assert_eq!(fill.implicit_writes(), b(&[2, 7, 10, 11, 12, 13, 14]));
+
+    let pixblt = decode("PIXBLT B,L");
+    assert_eq!(pixblt.implicit_reads(), b(&[0, 1, 2, 3, 7, 8, 9]));
+    assert_eq!(pixblt.implicit_writes(), b(&[0, 2, 10, 11, 12, 13, 14]));
+
+    // the explicit operands aren't included
+    let drav = decode("DRAV A1, A2");
+    assert_eq!(drav.implicit_reads(), b(&[4, 5, 6, 9]));
+    assert!(drav.implicit_writes().is_empty());
+
+    let add = decode("ADD B0, B1");
+    assert!(add.implicit_reads().is_empty());
+    assert!(add.implicit_writes().is_empty());
+}
+
+#[test]
+fn registers_have_graphics_names() {
+    assert_eq!(Reg::B(0).graphics_name(), Some("SADDR"));
+    assert_eq!(Reg::B(7).graphics_name(), Some("DYDX"));
+    assert_eq!(Reg::B(14).graphics_name(), Some("TEMP"));
+    assert_eq!(Reg::A(0).graphics_name(), None);
+    assert_eq!(Reg::from_number(31), Reg::Sp);
+    assert_eq!(Reg::from_number(17).to_string(), "B1");
+}
+
+#[test]
+fn listing_annotates_only_when_asked() {
+    let source = "FILL L\nCPW A1, A3\nRETS\n";
+    let bytes = assemble(source, BitAddress(0)).unwrap().to_bytes();
+
+    let plain = disassemble_stage2_with(
+        disassemble_stage1(&bytes, BitAddress(0)),
+        &Listing::default(),
+    );
+    assert!(!plain.contains(';'));
+
+    let listing = Listing {
+        implicit_registers: true,
+        ..Listing::default()
+    };
+    let annotated = disassemble_stage2_with(disassemble_stage1(&bytes, BitAddress(0)), &listing);
+    let comments: Vec<_> = annotated
+        .lines()
+        .map(|line| line.split_once("; ").map_or("", |(_, comment)| comment))
+        .collect();
+    assert_eq!(
+        comments,
+        [
+            "reads DADDR DPTCH DYDX COLOR1, writes DADDR COUNT INC1 INC2 PATTRN TEMP",
+            "reads WSTART WEND",
+            "",
+        ]
+    );
+    assert_eq!(
+        assemble_listing(&annotated, None).unwrap().to_bytes(),
+        bytes
+    );
+}
@@ -0,0 +1,68 @@
+use tms34010_asm::asm::{assemble, assemble_listing};
+use tms34010_asm::disasm::{auto_labels, disassemble_stage1, disassemble_stage2_labeled, to_words};
+use tms34010_asm::io_registers::{io_register_address, io_register_name, io_registers};
+use tms34010_asm::symbol::BitAddress;
+
+#[test]
+fn looks_up_registers_both_ways() {
+    assert_eq!(io_register_name(BitAddress(0xc0000000)), Some("HESYNC"));
+    assert_eq!(io_register_name(BitAddress(0xc0000080)), Some("DPYCTL"));
+    assert_eq!(io_register_name(BitAddress(0xc00001e0)), Some("REFCNT"));
+    // reserved, the middle of a register and past the end
+    assert_eq!(io_register_name(BitAddress(0xc0000170)), None);
+    assert_eq!(io_register_name(BitAddress(0xc0000088)), None);
+    assert_eq!(io_register_name(BitAddress(0xc0000200)), None);
+
+    assert_eq!(io_register_address("intpend"), Some(BitAddress(0xc0000120)));
+    assert_eq!(io_register_address("HSTCTLH"), Some(BitAddress(0xc0000100)));
+    assert_eq!(io_register_address("FOO"), None);
+    assert_eq!(io_registers().count(), 27);
+}
+
+#[test]
+fn operands_name_the_registers() {
+    let source = "
+        MOVE    A0, @0C0000080h, 0
+        MOVI    0C00000B0h, A1
+        MOVE    @0C0000088h, A2, 0
+        RETS
+";
+    let start = BitAddress(0xffc00000);
+    let bytes = assemble(source, start).unwrap().to_bytes();
+    let inst_vec = disassemble_stage1(&bytes, start);
+    let labels = auto_labels(&to_words(&bytes).0, start, &inst_vec);
+    let listing = disassemble_stage2_labeled(inst_vec, &labels);
+    let lines: Vec<_> = listing
+        .lines()
+        .map(|line| line.split("  ").next().unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "FFC00000:\tDPYCTL .set C0000080h",
+            "FFC00000:\tCONTROL .set C00000B0h",
+            "FFC00000:\tMOVE A0, @DPYCTL, 0",
+            "FFC00030:\tMOVI CONTROL, A1, L",
+            // not the start of a register
+            "FFC00060:\tMOVE @C0000088h, A2, 0",
+            "FFC00090:\tRETS",
+        ]
+    );
+    assert_eq!(assemble_listing(&listing, None).unwrap().to_bytes(), bytes);
+}
+
+#[test]
+fn the_assembler_knows_the_registers() {
+    let by_name = assemble(" MOVE A0, @DPYCTL, 0\n MOVI INTPEND, A1", BitAddress(0)).unwrap();
+    let by_address = assemble(
+        " MOVE A0, @0C0000080h, 0\n MOVI 0C0000120h, A1",
+        BitAddress(0),
+    )
+    .unwrap();
+    assert_eq!(by_name.words, by_address.words);
+
+    // but the source's own names come first
+    let source = "DPYCTL .equ 100h\n MOVI DPYCTL, A0\nCONTROL: JRUC CONTROL";
+    let own = assemble(source, BitAddress(0)).unwrap();
+    assert_eq!(own.words, [0x09c0, 0x0100, 0xc0ff]);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use tms34010_asm::asm::assemble_listing;
+use tms34010_asm::disasm::{
+    auto_labels, branch_labels, disassemble_stage1, disassemble_stage2_labeled,
+};
+use tms34010_asm::symbol::BitAddress;
+
+use common::to_bytes;
+
+// At 1000h: CALLR 1050h, JRNE back to the start, JRUC to the RETS the long
+// way, DSJS B0 back to the JRNE, the subroutine's RETS
+const PROGRAM: [u16; 6] = [0x0d3f, 0x0003, 0xcbfd, 0xc000, 0x0000, 0x0960];
+
+fn listing() -> String {
+    let start = BitAddress(0x1000);
+    let inst_vec = disassemble_stage1(&to_bytes(&PROGRAM), start);
+    let labels = auto_labels(&PROGRAM, start, &inst_vec);
+    disassemble_stage2_labeled(inst_vec, &labels)
+}
+
+#[test]
+fn branch_targets_get_labels() {
+    let inst_vec = disassemble_stage1(&to_bytes(&PROGRAM), BitAddress(0x1000));
+    let labels = branch_labels(&inst_vec);
+    let labels: Vec<_> = labels
+        .iter()
+        .map(|(address, label)| (address.0, label.as_str()))
+        .collect();
+    // the RETS is jumped to as well as called, SUB_ wins
+    assert_eq!(labels, [(0x1000, "L_00001000"), (0x1050, "SUB_00001050")]);
+}
+
+#[test]
+fn operands_use_the_labels() {
+    let listing = listing();
+    let lines: Vec<_> = listing
+        .lines()
+        .map(|line| line.split("  ").next().unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "00001000:\tL_00001000:",
+            "00001000:\tCALLR SUB_00001050",
+            "00001020:\tJRNE L_00001000",
+            "00001030:\tJRUC SUB_00001050, L",
+            "00001050:\tSUB_00001050:",
+            "00001050:\tRETS",
+        ]
+    );
+}
+
+#[test]
+fn labeled_listing_reassembles() {
+    let assembly = assemble_listing(&listing(), None).unwrap();
+    assert_eq!(assembly.words, PROGRAM);
+    assert_eq!(assembly.symbols.get("SUB_00001050").copied(), Some(0x1050));
+}
+
+#[test]
+fn targets_between_instructions_stay_addresses() {
+    // JRUC into the middle of the MOVI, which doesn't get a label line so
+    // can't be referred to either
+    let words = [0xc001, 0x09e0, 0x0000, 0x1000];
+    let start = BitAddress(0);
+    let inst_vec = disassemble_stage1(&to_bytes(&words), start);
+    let labels = auto_labels(&words, start, &inst_vec);
+    let listing = disassemble_stage2_labeled(inst_vec, &labels);
+    assert!(listing.starts_with("00000000:\tJRUC 20h "));
+    assert_eq!(assemble_listing(&listing, None).unwrap().words, words);
+}
@@ -0,0 +1,207 @@
+use tms34010_asm::asm::assemble;
+use tms34010_asm::disasm::Decoder;
+use tms34010_asm::symbol::BitAddress;
+
+// The TMS34010 opcode map from the user's guide, one line per encoding of the
+// first instruction word. 0 and 1 are fixed bits, anything else is a field.
+// The first matching line wins, and words matching nothing are reserved and
+// should come out as DW. Where the decoder prefers an alias the accepted
+// mnemonics are separated by '|'.
+const OPCODE_MAP: &[(&str, &str)] = &[
+    ("0000 0000 001R DDDD", "REV"),
+    ("0000 0001 0000 0000", "EMU"),
+    ("0000 0001 001R DDDD", "EXGPC"),
+    ("0000 0001 010R DDDD", "GETPC"),
+    ("0000 0001 011R DDDD", "JUMP"),
+    ("0000 0001 100R DDDD", "GETST"),
+    ("0000 0001 101R DDDD", "PUTST"),
+    ("0000 0001 1100 0000", "POPST"),
+    ("0000 0001 1110 0000", "PUSHST"),
+    ("0000 0011 0000 0000", "NOP"),
+    ("0000 0011 0010 0000", "CLRC"),
+    ("0000 0011 0100 0000", "MOVB"),
+    ("0000 0011 0110 0000", "DINT"),
+    ("0000 0011 100R DDDD", "ABS"),
+    ("0000 0011 101R DDDD", "NEG"),
+    ("0000 0011 110R DDDD", "NEGB"),
+    ("0000 0011 111R DDDD", "NOT"),
+    ("0000 01F1 000R DDDD", "SEXT"),
+    ("0000 01F1 001R DDDD", "ZEXT"),
+    ("0000 01F1 01ES SSSS", "SETF"),
+    ("0000 01F1 100R SSSS", "MOVE"),
+    ("0000 01F1 101R DDDD", "MOVE"),
+    ("0000 01F1 1100 0000", "MOVE"),
+    ("0000 0101 111R SSSS", "MOVB"),
+    ("0000 0111 111R DDDD", "MOVB"),
+    ("0000 1001 000N NNNN", "TRAP"),
+    ("0000 1001 001R SSSS", "CALL"),
+    ("0000 1001 0100 0000", "RETI"),
+    ("0000 1001 011N NNNN", "RETS"),
+    ("0000 1001 100R DDDD", "MMTM"),
+    ("0000 1001 101R DDDD", "MMFM"),
+    ("0000 1001 110R DDDD", "MOVI"),
+    ("0000 1001 111R DDDD", "MOVI"),
+    ("0000 1011 000R DDDD", "ADDI"),
+    ("0000 1011 001R DDDD", "ADDI"),
+    ("0000 1011 010R DDDD", "CMPI"),
+    ("0000 1011 011R DDDD", "CMPI"),
+    ("0000 1011 100R DDDD", "ANDI"),
+    ("0000 1011 101R DDDD", "ORI"),
+    ("0000 1011 110R DDDD", "XORI"),
+    ("0000 1011 111R DDDD", "SUBI"),
+    ("0000 1101 000R DDDD", "SUBI"),
+    ("0000 1101 0011 1111", "CALLR"),
+    ("0000 1101 0101 1111", "CALLA"),
+    ("0000 1101 0110 0000", "EINT"),
+    ("0000 1101 100R DDDD", "DSJ"),
+    ("0000 1101 101R DDDD", "DSJEQ"),
+    ("0000 1101 110R DDDD", "DSJNE"),
+    ("0000 1101 1110 0000", "SETC"),
+    ("0000 1111 0000 0000", "PIXBLT L,L"),
+    ("0000 1111 0010 0000", "PIXBLT L,XY"),
+    ("0000 1111 0100 0000", "PIXBLT XY,L"),
+    ("0000 1111 0110 0000", "PIXBLT XY,XY"),
+    ("0000 1111 1000 0000", "PIXBLT B,L"),
+    ("0000 1111 1010 0000", "PIXBLT B,XY"),
+    ("0000 1111 1100 0000", "FILL L"),
+    ("0000 1111 1110 0000", "FILL XY"),
+    ("0001 0000 001R DDDD", "INC"),
+    ("0001 00KK KKKR DDDD", "ADDK"),
+    ("0001 0100 001R DDDD", "DEC"),
+    ("0001 01KK KKKR DDDD", "SUBK"),
+    ("0001 10KK KKKR DDDD", "MOVK"),
+    ("0001 11KK KKKR DDDD", "BTST"),
+    ("0010 00KK KKKR DDDD", "SLAK"),
+    ("0010 01KK KKKR DDDD", "SLLK"),
+    ("0010 10KK KKKR DDDD", "SRAK"),
+    ("0010 11KK KKKR DDDD", "SRLK"),
+    ("0011 00KK KKKR DDDD", "RLK"),
+    // DSJS with a backward offset of 0 is the same branch as a forward one,
+    // the decoder only accepts the forward encoding
+    ("0011 1100 000R DDDD", "DW"),
+    ("0011 1DKK KKKR DDDD", "DSJS"),
+    ("0100 000S SSSR DDDD", "ADD"),
+    ("0100 001S SSSR DDDD", "ADDC"),
+    ("0100 010S SSSR DDDD", "SUB"),
+    ("0100 011S SSSR DDDD", "SUBB"),
+    ("0100 100S SSSR DDDD", "CMP"),
+    ("0100 101S SSSR DDDD", "BTST"),
+    ("0100 11MS SSSR DDDD", "MOVE"),
+    ("0101 000S SSSR DDDD", "AND"),
+    ("0101 001S SSSR DDDD", "ANDN"),
+    ("0101 010S SSSR DDDD", "OR"),
+    ("0101 011S SSSR DDDD", "XOR|CLR"),
+    ("0101 100S SSSR DDDD", "DIVS"),
+    ("0101 101S SSSR DDDD", "DIVU"),
+    ("0101 110S SSSR DDDD", "MPYS"),
+    ("0101 111S SSSR DDDD", "MPYU"),
+    ("0110 000S SSSR DDDD", "SLA"),
+    ("0110 001S SSSR DDDD", "SLL"),
+    ("0110 010S SSSR DDDD", "SRA"),
+    ("0110 011S SSSR DDDD", "SRL"),
+    ("0110 100S SSSR DDDD", "RL"),
+    ("0110 101S SSSR DDDD", "LMO"),
+    ("0110 110S SSSR DDDD", "MODS"),
+    ("0110 111S SSSR DDDD", "MODU"),
+    ("1000 00FS SSSR DDDD", "MOVE"),
+    ("1000 01FS SSSR DDDD", "MOVE"),
+    ("1000 10FS SSSR DDDD", "MOVE"),
+    ("1000 110S SSSR DDDD", "MOVB"),
+    ("1000 111S SSSR DDDD", "MOVB"),
+    ("1001 00FS SSSR DDDD", "MOVE"),
+    ("1001 01FS SSSR DDDD", "MOVE"),
+    ("1001 10FS SSSR DDDD", "MOVE"),
+    ("1001 110S SSSR DDDD", "MOVB"),
+    ("1010 00FS SSSR DDDD", "MOVE"),
+    ("1010 01FS SSSR DDDD", "MOVE"),
+    ("1010 10FS SSSR DDDD", "MOVE"),
+    ("1010 110S SSSR DDDD", "MOVB"),
+    ("1010 111S SSSR DDDD", "MOVB"),
+    ("1011 00FS SSSR DDDD", "MOVE"),
+    ("1011 01FS SSSR DDDD", "MOVE"),
+    ("1011 10FS SSSR DDDD", "MOVE"),
+    ("1011 110S SSSR DDDD", "MOVB"),
+    ("1100 CCCC 1000 0000", "JA"),
+    ("1100 CCCC OOOO OOOO", "JR"),
+    ("1101 00FS SSSR DDDD", "MOVE"),
+    ("1101 01F0 000R DDDD", "MOVE"),
+    ("1101 01F1 000R DDDD", "EXGF"),
+    ("1101 1111 0001 1010", "LINE"),
+    ("1101 1111 1001 1010", "LINE"),
+    ("1110 000S SSSR DDDD", "ADDXY"),
+    ("1110 001S SSSR DDDD", "SUBXY"),
+    ("1110 010S SSSR DDDD", "CMPXY"),
+    ("1110 011S SSSR DDDD", "CPW"),
+    ("1110 100S SSSR DDDD", "CVXYL"),
+    ("1110 110S SSSR DDDD", "MOVX"),
+    ("1110 111S SSSR DDDD", "MOVY"),
+    ("1111 000S SSSR DDDD", "PIXT"),
+    ("1111 001S SSSR DDDD", "PIXT"),
+    ("1111 010S SSSR DDDD", "PIXT"),
+    ("1111 011S SSSR DDDD", "DRAV"),
+    ("1111 100S SSSR DDDD", "PIXT"),
+    ("1111 101S SSSR DDDD", "PIXT"),
+    ("1111 110S SSSR DDDD", "PIXT"),
+];
+
+fn expected(word: u16) -> &'static str {
+    for (pattern, mnemonic) in OPCODE_MAP {
+        let bits = pattern.chars().filter(|c| *c != ' ');
+        let matches = bits.enumerate().all(|(i, c)| {
+            let bit = word >> (15 - i) & 1;
+            match c {
+                '0' => bit == 0,
+                '1' => bit == 1,
+                _ => true,
+            }
+        });
+        if matches {
+            return mnemonic;
+        }
+    }
+    "DW"
+}
+
+// Walks every possible first word and checks the decoder against the map.
+// The operand words are chosen so every long form is canonical (a zero
+// offset for long JR would be the short form's job otherwise, so use 0x100)
+// and all mismatches are reported together.
+#[test]
+fn every_first_word_matches_the_opcode_map() {
+    let mut mismatches = vec![];
+    for word in 0..=0xffffu16 {
+        let words = [word, 0x0100, 0x0000, 0x0000, 0x0000];
+        // strictly, the lenient decoder ignores don't-care bits
+        let mnemonic = match Decoder::try_decode_one(&words, BitAddress(0)) {
+            Ok((instruction, _)) => instruction.get_mnemonic(),
+            Err(_) => "DW",
+        };
+        let expected = expected(word);
+        if !expected.split('|').any(|m| m == mnemonic) {
+            mismatches.push(format!(
+                "{:04X}: expected {}, got {}",
+                word, expected, mnemonic
+            ));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "{} mismatches:\n{}",
+        mismatches.len(),
+        mismatches.join("\n")
+    );
+}
+
+// 0B80h is ANDNI in the opcode map, ANDI is the same opcode with the
+// immediate complemented by the assembler
+#[test]
+fn andni_and_andi_share_an_opcode() {
+    let andni = assemble("ANDNI 0FFh, A1", BitAddress(0))
+        .unwrap()
+        .to_bytes();
+    let andi = assemble("ANDI 0FFFFFF00h, A1", BitAddress(0))
+        .unwrap()
+        .to_bytes();
+    assert_eq!(andni, [0x81, 0x0b, 0xff, 0x00, 0x00, 0x00]);
+    assert_eq!(andni, andi);
+}
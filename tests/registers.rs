@@ -0,0 +1,84 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use tms34010_asm::register::Reg::{self, *};
+
+use common::decode;
+
+fn set(regs: &[Reg]) -> BTreeSet<Reg> {
+    regs.iter().copied().collect()
+}
+
+#[test]
+fn alu_instructions_use_operands_and_flags() {
+    let addc = decode("ADDC B1, B2");
+    assert_eq!(addc.reads(), set(&[B(1), B(2), C]));
+    assert_eq!(addc.writes(), set(&[B(2), N, C, Z, V]));
+
+    let and = decode("AND A3, SP");
+    assert_eq!(and.reads(), set(&[A(3), Sp]));
+    assert_eq!(and.writes(), set(&[Sp, Z]));
+
+    // an even Rd takes the 64 bit result in the pair, the size of Rs is FS1
+    let mpys = decode("MPYS A1, A4");
+    assert_eq!(mpys.reads(), set(&[A(1), A(4), Fs1]));
+    assert_eq!(mpys.writes(), set(&[A(4), A(5), N, Z]));
+    let divu = decode("DIVU A1, A5");
+    assert_eq!(divu.writes(), set(&[A(5), Z, V]));
+}
+
+#[test]
+fn moves_use_the_field_settings() {
+    let load = decode("MOVE *A0+, A1, 1");
+    assert_eq!(load.reads(), set(&[A(0), Fs1, Fe1]));
+    assert_eq!(load.writes(), set(&[A(0), A(1), N, Z, V]));
+
+    let store = decode("MOVE A1, *A0, 0");
+    assert_eq!(store.reads(), set(&[A(0), A(1), Fs0]));
+    assert!(store.writes().is_empty());
+
+    let setf = decode("SETF 8, 0, 1");
+    assert!(setf.reads().is_empty());
+    assert_eq!(setf.writes(), set(&[Fs1, Fe1]));
+
+    let mmtm = decode("MMTM SP, B0, B1, B14");
+    assert_eq!(mmtm.reads(), set(&[Sp, B(0), B(1), B(14)]));
+    assert_eq!(mmtm.writes(), set(&[Sp]));
+    let mmfm = decode("MMFM SP, A0, A9, A14");
+    assert_eq!(mmfm.reads(), set(&[Sp]));
+    assert_eq!(mmfm.writes(), set(&[Sp, A(0), A(9), A(14)]));
+}
+
+#[test]
+fn control_flow_uses_pc_sp_and_conditions() {
+    let jr = decode("JRLE 1100h");
+    assert_eq!(jr.reads(), set(&[N, Z, V, Pc]));
+    assert_eq!(jr.writes(), set(&[Pc]));
+
+    let call = decode("CALLA 2000h");
+    assert_eq!(call.reads(), set(&[Sp, Pc]));
+    assert_eq!(call.writes(), set(&[Sp, Pc]));
+
+    let reti = decode("RETI");
+    assert_eq!(reti.reads(), set(&[Sp]));
+    assert_eq!(
+        reti.writes(),
+        set(&[N, C, Z, V, Fs0, Fe0, Fs1, Fe1, Sp, Pc])
+    );
+
+    let dsjeq = decode("DSJEQ A2, 1000h");
+    assert_eq!(dsjeq.reads(), set(&[A(2), Z, Pc]));
+    assert_eq!(dsjeq.writes(), set(&[A(2), Pc]));
+}
+
+#[test]
+fn graphics_instructions_include_the_implicit_registers() {
+    let drav = decode("DRAV A1, A2");
+    assert_eq!(drav.reads(), set(&[A(1), A(2), B(4), B(5), B(6), B(9)]));
+    assert_eq!(drav.writes(), set(&[A(2), V]));
+
+    assert_eq!(decode("NOP").reads(), set(&[]));
+    assert_eq!(Pc.to_string(), "PC");
+    assert_eq!(Fe1.to_string(), "FE1");
+}
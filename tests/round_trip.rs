@@ -0,0 +1,98 @@
+mod common;
+
+use tms34010_asm::asm::assemble_listing;
+use tms34010_asm::disasm::{
+    auto_labels, disassemble_stage1, disassemble_stage2, disassemble_stage2_labeled,
+};
+use tms34010_asm::symbol::BitAddress;
+use tms34010_asm::Error;
+
+use common::to_bytes;
+
+// Every opcode (with a few register fields each) followed by a couple of
+// different operand words, disassembled and fed back through the assembler.
+// The NOPs at the end of each chunk soak up the longest instruction so the
+// chunks stay independent.
+#[test]
+fn listing_reassembles_to_the_same_bytes() {
+    let mut listing = String::new();
+    let mut original = vec![];
+    let mut pc = 0x100;
+    for opcode in 0..2048u16 {
+        for regs in [0x00, 0x0f, 0x15, 0x1f] {
+            for operands in [
+                [0x1234, 0x8765, 0x0010, 0xffc0],
+                [0x0001, 0x8000, 0x7fff, 0xffff],
+            ] {
+                let mut words = vec![opcode << 5 | regs];
+                words.extend_from_slice(&operands);
+                words.extend_from_slice(&[0x0300; 4]);
+                let bytes = to_bytes(&words);
+                listing.push_str(&disassemble_stage2(disassemble_stage1(
+                    &bytes,
+                    BitAddress::from_word(pc),
+                )));
+                original.extend_from_slice(&bytes);
+                pc += words.len();
+            }
+        }
+    }
+
+    let assembly = assemble_listing(&listing, None).unwrap_or_else(|e| match e {
+        Error::OutOfRange(e) | Error::Parse(e) => {
+            let line = listing.lines().nth(e.line - 1).unwrap_or("");
+            panic!("{}\n{}", e, line)
+        }
+        e => panic!("{}", e),
+    });
+    let bytes = assembly.to_bytes();
+    assert_eq!(bytes.len(), original.len());
+    if let Some(i) = (0..bytes.len()).find(|&i| bytes[i] != original[i]) {
+        let address = (0x100 + i / 2) * 16;
+        let line = listing
+            .lines()
+            .take_while(|line| usize::from_str_radix(&line[..8], 16).unwrap() <= address)
+            .last()
+            .unwrap();
+        panic!("mismatch at byte {:X} near\n{}", i, line);
+    }
+}
+
+#[test]
+fn ambiguous_forms_keep_their_encoding() {
+    let words = [
+        0xc000, 0x0000, // JRUC to the next instruction, long form
+        0x09e0, 0x0001, 0x0000, // MOVI 1, A0 with a long immediate
+        0x0b40, 0xfffe, // CMPI 1, A0 as a word
+        0x101f, // ADDK 32, B15
+        0x4e1f, // MOVE B0, SP across files
+        0x0300, // NOP
+    ];
+    let bytes = to_bytes(&words);
+    let listing = disassemble_stage2(disassemble_stage1(&bytes, BitAddress(0x80000)));
+    let assembly = assemble_listing(&listing, None).unwrap();
+    assert_eq!(assembly.origin, BitAddress(0x80000));
+    assert_eq!(assembly.words, words);
+}
+
+// Random words have branches all over the place, including long JRs to labels
+// further on than anything the assembler has placed yet
+#[test]
+fn labeled_listing_reassembles_to_the_same_bytes() {
+    let mut state = 0xdead_beefu32;
+    let words: Vec<u16> = (0..16384)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u16
+        })
+        .collect();
+    let start = BitAddress(0xffc00000);
+    let inst_vec = disassemble_stage1(&to_bytes(&words), start);
+    let labels = auto_labels(&words, start, &inst_vec);
+    assert!(labels.len() > 1000);
+    let listing = disassemble_stage2_labeled(inst_vec, &labels);
+    let assembly = assemble_listing(&listing, None).unwrap();
+    assert_eq!(assembly.words, words);
+}
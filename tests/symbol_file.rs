@@ -0,0 +1,198 @@
+use tms34010_asm::asm::{assemble, assemble_listing};
+use tms34010_asm::disasm::{
+    branch_labels, disassemble_stage1, disassemble_stage2_labeled, disassemble_stage2_with, Listing,
+};
+use tms34010_asm::symbol::BitAddress;
+use tms34010_asm::symbol_file::{parse_symbols, symbol_kinds, symbol_labels, SymbolKind};
+use tms34010_asm::Error;
+
+const SYMBOLS: &str = "\
+; a comment
+FFC00000h  reset     code
+0x1000000  ram_base  LONG   # another
+C0000000h  hesync
+100h       low_thing data
+1000020h   flags     word
+";
+
+#[test]
+fn parses_addresses_names_and_kinds() {
+    let symbols = parse_symbols(SYMBOLS, None).unwrap();
+    let found: Vec<_> = symbols
+        .iter()
+        .map(|symbol| (symbol.address.0, symbol.name.as_str(), symbol.kind))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (0xffc00000, "reset", Some(SymbolKind::Code)),
+            (0x1000000, "ram_base", Some(SymbolKind::Long)),
+            (0xc0000000, "hesync", None),
+            (0x100, "low_thing", Some(SymbolKind::Data)),
+            (0x1000020, "flags", Some(SymbolKind::Word)),
+        ]
+    );
+}
+
+#[test]
+fn bad_lines_are_reported_with_their_line() {
+    for (text, message) in [
+        ("10h a\nzz b", "line 2: 'zz' is not a valid address (column 1)"),
+        ("10h 1a", "line 1: '1a' is not a valid name (column 5)"),
+        ("10h A3", "line 1: 'A3' is not a valid name (column 5)"),
+        ("10h a\n20h a", "line 2: 'a' is already defined (column 5)"),
+        ("10h a byte", "line 1: unknown kind 'byte', expected code, data, word or long (column 7)"),
+        ("10h a code more", "line 1: expected an address, a name and optionally code, data, word or long (column 1)"),
+    ] {
+        match parse_symbols(text, None) {
+            Err(Error::Parse(e)) => assert_eq!(e.to_string(), message),
+            other => panic!("{:?}", other),
+        }
+    }
+}
+
+#[test]
+fn operands_use_the_symbols() {
+    let source = "
+start:  MOVI    1000000h, A0
+        MOVI    100h, A1
+        MOVE    @0C0000000h, A2, 1
+        MOVB    A2, @1000020h
+        CALLA   sub
+        JRUC    start
+sub:    RETS
+";
+    let start = BitAddress(0xffc00000);
+    let bytes = assemble(source, start).unwrap().to_bytes();
+    let labels = symbol_labels(&parse_symbols(SYMBOLS, None).unwrap());
+    let listing = disassemble_stage2_labeled(disassemble_stage1(&bytes, start), &labels);
+    let lines: Vec<_> = listing
+        .lines()
+        .map(|line| line.split("  ").next().unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            // only the symbols that are used and aren't in the listing
+            "FFC00000:\tlow_thing .set 100h",
+            "FFC00000:\tram_base .set 1000000h",
+            "FFC00000:\tflags .set 1000020h",
+            "FFC00000:\thesync .set C0000000h",
+            "FFC00000:\treset:",
+            "FFC00000:\tMOVI ram_base, A0, L",
+            "FFC00030:\tMOVI low_thing, A1, W",
+            "FFC00050:\tMOVE @hesync, A2, 1",
+            "FFC00080:\tMOVB A2, @flags",
+            "FFC000B0:\tCALLA FFC000F0h",
+            "FFC000E0:\tJRUC reset",
+            "FFC000F0:\tRETS",
+        ]
+    );
+    assert_eq!(assemble_listing(&listing, None).unwrap().to_bytes(), bytes);
+}
+
+// The listing of `bytes` with the symbols and then the generated branch
+// labels, as the disassembler puts them together, without the hexdump
+fn list_with_symbols(bytes: &[u8], start: BitAddress, symbols: &str) -> (String, Vec<String>) {
+    let symbols = parse_symbols(symbols, None).unwrap();
+    let decoded = disassemble_stage1(bytes, start);
+    let mut labels = symbol_labels(&symbols);
+    for (address, label) in branch_labels(&decoded) {
+        labels.entry(address).or_insert(label);
+    }
+    let listing = Listing {
+        labels,
+        kinds: symbol_kinds(&symbols),
+        ..Listing::default()
+    };
+    let text = disassemble_stage2_with(decoded, &listing);
+    let lines = text
+        .lines()
+        .map(|line| line.split("  ").next().unwrap().to_string())
+        .collect();
+    (text, lines)
+}
+
+#[test]
+fn data_symbols_are_not_decoded() {
+    // the .word on its own would be the start of a MOVI and the .long a
+    // MOVE, which would each swallow what follows
+    let source = "
+        RETS
+        .word   09E0h
+        .long   12345678h
+        .word   09C0h, 0300h
+        RETS
+";
+    let start = BitAddress(0x1000);
+    let bytes = assemble(source, start).unwrap().to_bytes();
+    let (text, lines) = list_with_symbols(
+        &bytes,
+        start,
+        "1010h table word\n1020h value long\n1040h text data\n1060h done",
+    );
+    assert_eq!(
+        lines,
+        [
+            "00001000:\tRETS",
+            "00001010:\ttable:",
+            "00001010:\t.word 09E0h",
+            "00001020:\tvalue:",
+            "00001020:\t.long 12345678h",
+            "00001040:\ttext:",
+            "00001040:\tDW 09C0h",
+            "00001050:\tDW 0300h",
+            "00001060:\tdone:",
+            "00001060:\tRETS",
+        ]
+    );
+    assert_eq!(assemble_listing(&text, None).unwrap().to_bytes(), bytes);
+}
+
+#[test]
+fn data_runs_to_the_next_symbol_not_the_next_label() {
+    // the JRP in the data would label 30h and the rest would be a MOVI
+    let source = " NOP\n .word 0C001h, 0300h, 09E0h, 1234h, 5678h";
+    let bytes = assemble(source, BitAddress(0)).unwrap().to_bytes();
+    let (text, lines) = list_with_symbols(&bytes, BitAddress(0), "10h blob data");
+    assert_eq!(
+        lines,
+        [
+            "00000000:\tNOP",
+            "00000010:\tblob:",
+            "00000010:\tDW C001h",
+            "00000020:\tDW 0300h",
+            "00000030:\tDW 09E0h",
+            "00000040:\tDW 1234h",
+            "00000050:\tDW 5678h",
+        ]
+    );
+    assert_eq!(assemble_listing(&text, None).unwrap().to_bytes(), bytes);
+}
+
+#[test]
+fn data_symbols_with_an_odd_byte_at_the_end() {
+    let bytes = [0x00, 0x03, 0x00, 0x03, 0x00, 0x03, 0x42];
+    for symbols in ["10h tbl word", "20h tbl long", "30h tail data"] {
+        let (text, lines) = list_with_symbols(&bytes, BitAddress(0), symbols);
+        assert_eq!(lines.last().unwrap(), "00000030:\t.byte 42h", "{}", symbols);
+        assert_eq!(assemble_listing(&text, None).unwrap().to_bytes(), bytes);
+    }
+}
+
+#[test]
+fn data_symbols_at_the_top_of_memory() {
+    // the last three words, ending where the address space wraps
+    let start = BitAddress(0xffffffd0);
+    let bytes = [0x00, 0x03, 0xe0, 0x09, 0x34, 0x12];
+    let (text, lines) = list_with_symbols(&bytes, start, "FFFFFFE0h top long");
+    assert_eq!(
+        lines,
+        [
+            "FFFFFFD0:\tNOP",
+            "FFFFFFE0:\ttop:",
+            "FFFFFFE0:\t.long 123409E0h",
+        ]
+    );
+    assert_eq!(assemble_listing(&text, None).unwrap().to_bytes(), bytes);
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use tms34010_asm::asm::assemble_listing;
+use tms34010_asm::disasm::{disassemble_stage1, disassemble_stage2};
+use tms34010_asm::instruction::Instruction;
+use tms34010_asm::symbol::BitAddress;
+use tms34010_asm::symbol::IW;
+
+use common::to_bytes;
+
+// Every instruction with extension words, cut off after each of them in turn.
+// What's left has to come out as one DW per word rather than a panic.
+#[test]
+fn cut_off_instructions_become_data() {
+    let operands = [0x0100, 0x0000, 0x0000, 0x0000];
+    for first in 0..=0xffffu16 {
+        let mut words = vec![first];
+        words.extend_from_slice(&operands);
+        let full = disassemble_stage1(&to_bytes(&words), BitAddress(0));
+        let len = full[0].words().len();
+        for cut in 1..len {
+            let decoded = disassemble_stage1(&to_bytes(&words[..cut]), BitAddress(0));
+            assert_eq!(decoded.len(), cut, "{:04X} cut to {} words", first, cut);
+            for (i, decoded) in decoded.iter().enumerate() {
+                assert_eq!(decoded.pc, i);
+                assert!(matches!(decoded.instruction, Instruction::Dw(IW(w)) if w == words[i]));
+                assert_eq!(decoded.words(), [words[i]]);
+            }
+        }
+    }
+}
+
+// MOVI IL with one whole extension word and half of the next
+#[test]
+fn odd_trailing_byte_is_data() {
+    let bytes = [0xe0, 0x09, 0x34, 0x12, 0x56];
+    let decoded = disassemble_stage1(&bytes, BitAddress(0x100));
+    let listing = disassemble_stage2(decoded);
+    let lines: Vec<_> = listing
+        .lines()
+        .map(|line| line.split("  ").next().unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "00000100:\tDW 09E0h",
+            "00000110:\tDW 1234h",
+            "00000120:\t.byte 56h",
+        ]
+    );
+
+    // and it comes back as just the one byte
+    let assembly = assemble_listing(&listing, None).unwrap();
+    assert_eq!(assembly.to_bytes(), bytes);
+}
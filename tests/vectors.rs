@@ -0,0 +1,122 @@
+mod common;
+
+use tms34010_asm::asm::assemble_listing;
+use tms34010_asm::disasm::{
+    disassemble_stage1, disassemble_stage2_labeled, disassemble_stage2_with, Listing,
+};
+use tms34010_asm::symbol::BitAddress;
+use tms34010_asm::vectors::{read_vectors, trap_name, vector_address, vector_labels};
+
+use common::to_bytes;
+
+// TRAP 1, RETS and a NOP just below the vector table, which has reset pointing
+// at the TRAP, INT1 and NMI sharing the RETS and everything else unprogrammed
+fn rom() -> (BitAddress, Vec<u16>) {
+    let start = BitAddress(0xfffffbd0);
+    let mut words = vec![0x0901, 0x0960, 0x0300];
+    words.extend([0xffff; 64]);
+    let mut set = |trap: u8, handler: u32| {
+        let pos = ((vector_address(trap).0 - start.0) / 16) as usize;
+        words[pos] = handler as u16;
+        words[pos + 1] = (handler >> 16) as u16;
+    };
+    set(0, 0xfffffbd0);
+    set(1, 0xfffffbe0);
+    set(8, 0xfffffbe0);
+    (start, words)
+}
+
+#[test]
+fn reads_the_programmed_vectors() {
+    let (start, words) = rom();
+    let vectors = read_vectors(&words, start);
+    let found: Vec<_> = vectors
+        .iter()
+        .map(|vector| (vector.name(), vector.handler.0))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("RESET".to_string(), 0xfffffbd0),
+            ("INT1".to_string(), 0xfffffbe0),
+            ("NMI".to_string(), 0xfffffbe0),
+        ]
+    );
+    assert_eq!(vectors[0].address, BitAddress(0xffffffe0));
+    assert_eq!(vectors[2].address, BitAddress(0xfffffee0));
+
+    // a buffer that doesn't reach the table has none
+    assert!(read_vectors(&words[..40], start).is_empty());
+}
+
+#[test]
+fn names_every_trap() {
+    assert_eq!(trap_name(2), "INT2");
+    assert_eq!(trap_name(30), "ILLOP");
+    assert_eq!(trap_name(5), "TRAP5");
+    assert_eq!(vector_address(31), BitAddress(0xfffffc00));
+}
+
+#[test]
+fn handlers_are_labeled_in_the_listing() {
+    let (start, words) = rom();
+    let labels = vector_labels(&read_vectors(&words, start));
+    let listing = disassemble_stage2_labeled(disassemble_stage1(&to_bytes(&words), start), &labels);
+    let lines: Vec<_> = listing
+        .lines()
+        .take(5)
+        .map(|line| line.split("  ").next().unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "FFFFFBD0:\tRESET:",
+            "FFFFFBD0:\tTRAP 1",
+            // shared with NMI, INT1 comes first
+            "FFFFFBE0:\tINT1:",
+            "FFFFFBE0:\tRETS",
+            "FFFFFBF0:\tNOP",
+        ]
+    );
+
+    // the trap's name goes after the hexdump
+    let trap = listing.lines().nth(1).unwrap();
+    let (code, comment) = trap.split_once(';').unwrap();
+    assert!(code.trim_end().ends_with("0901"), "{}", trap);
+    assert_eq!(comment, " INT1");
+
+    let assembly = assemble_listing(&listing, None).unwrap();
+    assert_eq!(assembly.words, words);
+}
+
+#[test]
+fn traps_name_their_handler() {
+    let (start, words) = rom();
+    let vectors = read_vectors(&words, start);
+    let mut labels = vector_labels(&vectors);
+    labels.insert(BitAddress(0xfffffbe0), "irq".to_string());
+    let listing = Listing {
+        labels,
+        vectors,
+        ..Listing::default()
+    };
+    let text = disassemble_stage2_with(disassemble_stage1(&to_bytes(&words), start), &listing);
+    let trap = text.lines().nth(1).unwrap();
+    assert!(trap.ends_with("; INT1 -> irq"), "{}", trap);
+}
+
+#[test]
+fn zero_and_unmapped_vectors_are_not_handlers() {
+    let (start, mut words) = rom();
+    // INT2 at 0, HI and DI outside the buffer
+    for (trap, handler) in [(2u8, 0u32), (9, 0x1000), (10, 0xfff00000)] {
+        let pos = ((vector_address(trap).0 - start.0) / 16) as usize;
+        words[pos] = handler as u16;
+        words[pos + 1] = (handler >> 16) as u16;
+    }
+    let traps: Vec<_> = read_vectors(&words, start)
+        .iter()
+        .map(|vector| vector.trap)
+        .collect();
+    assert_eq!(traps, [0, 1, 8]);
+}